@@ -0,0 +1,85 @@
+use crate::{Record, Speed};
+
+/// Records slower than this speed in m/s are considered stationary and ignored
+const MIN_MOVING_SPEED: f64 = 0.5;
+
+/// Picks a speed limit that separates the work from the rest periods of a session.
+///
+/// The speeds of all records are split into two clusters using Otsu's method, which maximizes the
+/// variance between the clusters. This works well for interval sessions where the speed
+/// distribution is bimodal. Stationary records are ignored so standing still doesn't dominate
+/// the rest cluster. Returns `None` if there are fewer than two distinct speeds.
+///
+/// In a steady session without clear rest periods the speeds are not bimodal, and the limit
+/// separates moving slowly, e.g. while waiting at a crossing, from the rest of the session.
+pub fn auto_limit(records: &[Record]) -> Option<Speed> {
+    let mut speeds = records
+        .iter()
+        .map(|rec| rec.speed.to_ms())
+        .filter(|speed| speed.is_finite() && *speed >= MIN_MOVING_SPEED)
+        .collect::<Vec<_>>();
+    speeds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let count = speeds.len() as f64;
+    let total: f64 = speeds.iter().sum();
+
+    let mut best: Option<(f64, f64)> = None;
+    let mut lower_total = 0.0;
+    for split in 1..speeds.len() {
+        lower_total += speeds[split - 1];
+        if speeds[split - 1] == speeds[split] {
+            continue;
+        }
+
+        let lower_weight = split as f64 / count;
+        let upper_weight = 1.0 - lower_weight;
+        let lower_mean = lower_total / split as f64;
+        let upper_mean = (total - lower_total) / (count - split as f64);
+        let variance = lower_weight * upper_weight * (upper_mean - lower_mean).powi(2);
+
+        if best.is_none_or(|(best_variance, _)| variance > best_variance) {
+            best = Some((variance, (speeds[split - 1] + speeds[split]) / 2.0));
+        }
+    }
+
+    best.map(|(_, threshold)| Speed::Ms(threshold))
+}
+
+#[cfg(test)]
+mod test {
+    use super::auto_limit;
+    use crate::{IntervalDetector, Record, Speed};
+
+    #[test]
+    fn test_auto_limit() {
+        let records = [1.0, 1.2, 0.9, 4.0, 4.2, 1.1, 3.9, 4.1]
+            .iter()
+            .enumerate()
+            .map(|(time, &speed)| Record::new(time, 0.0, Speed::Ms(speed)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(auto_limit(&records), Some(Speed::Ms(2.55)));
+        assert_eq!(auto_limit(&records[..1]), None);
+    }
+
+    #[test]
+    fn test_auto_detect() {
+        // Jogging at 2 m/s with three reps of 30 seconds at 5 m/s, the last one until the end
+        let mut distance = 0.0;
+        let records = (0..300)
+            .map(|time| {
+                let speed = match time {
+                    60..=89 | 180..=209 | 270..=299 => 5.0,
+                    _ => 2.0,
+                };
+                distance += speed;
+                Record::new(time, distance, Speed::Ms(speed))
+            })
+            .collect::<Vec<_>>();
+
+        let limit = auto_limit(&records).unwrap();
+        assert_eq!(limit, Speed::Ms(3.5));
+        let ranges = IntervalDetector::new(limit).find_ranges(&records);
+        assert_eq!(ranges, vec![60..120, 180..240, 270..300]);
+    }
+}
@@ -0,0 +1,541 @@
+use crate::gaps::split_at_gaps;
+use crate::grade::grade_adjust;
+use crate::{Limit, Record, Speed, SpeedUnit, Sport, Thresholds};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::Serialize;
+use std::ops::Range;
+
+/// Finds the first interval at or after `start_index`.
+///
+/// An interval starts at the first record with a value of at least the start limit and ends as
+/// soon as the average value since its start drops below the stop limit, see [`Thresholds`], or
+/// with the last record. The average is weighted by the time each record covers so irregularly
+/// sampled records are averaged correctly. Records that lack the measured value, e.g. heart rate,
+/// are ignored.
+pub fn find_interval<T: Into<Thresholds>>(
+    records: &[Record],
+    start_index: usize,
+    thresholds: T,
+) -> Option<Range<usize>> {
+    let Thresholds {
+        start,
+        stop,
+        stop_duration,
+    } = thresholds.into();
+    let start_threshold = start.threshold();
+    let stop_threshold = stop.threshold();
+    let start_index = records
+        .iter()
+        .enumerate()
+        .skip(start_index)
+        .find_map(|(idx, rec)| match start.value(rec) {
+            Some(value) if value >= start_threshold => Some(idx),
+            _ => None,
+        })?;
+
+    let mut total = 0.0;
+    let mut total_time = 0.0;
+    let mut below_since = None;
+    for (idx, rec) in records.iter().enumerate().skip(start_index) {
+        let value = match stop.value(rec) {
+            Some(value) => value,
+            None => continue,
+        };
+        let duration = sample_duration(records, idx);
+        total += value * duration;
+        total_time += duration;
+        let average = if total_time > 0.0 {
+            total / total_time
+        } else {
+            value
+        };
+        // The first record is always part of the interval, so an interval is never empty
+        if idx > start_index && average < stop_threshold {
+            let end_index = *below_since.get_or_insert(idx);
+            if rec.time_in_seconds - records[end_index].time_in_seconds >= stop_duration {
+                return Some(start_index..end_index);
+            }
+        } else {
+            below_since = None;
+        }
+    }
+
+    // When the records run out, e.g. because the recording was paused, the interval ends with
+    // them unless the average already dropped below the stop limit
+    Some(start_index..below_since.unwrap_or(records.len()))
+}
+
+/// Returns the time in seconds that the record at `idx` covers, which is the time since the
+/// previous record. The first record covers the same time as the one after it.
+fn sample_duration(records: &[Record], idx: usize) -> f64 {
+    let (from, to) = match idx {
+        0 if records.len() < 2 => return 1.0,
+        0 => (&records[0], &records[1]),
+        idx => (&records[idx - 1], &records[idx]),
+    };
+    (to.time_in_seconds - from.time_in_seconds) as f64
+}
+
+/// Finds all consecutive intervals in `records`, see [`find_interval`].
+pub fn find_all_intervals<T: Into<Thresholds>>(
+    records: &[Record],
+    thresholds: T,
+) -> Vec<Range<usize>> {
+    let thresholds = thresholds.into();
+    let mut results = Vec::new();
+    let mut start_index = 0;
+    loop {
+        match find_interval(records, start_index, thresholds) {
+            Some(interval) => {
+                start_index = interval.end;
+                results.push(interval);
+            }
+            None => {
+                return results;
+            }
+        }
+    }
+}
+
+/// Summary of a detected interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntervalInfo {
+    /// Time in seconds since the start of the activity
+    pub start_time: usize,
+
+    /// Duration in seconds
+    pub duration: usize,
+
+    /// Distance in meters
+    pub distance: usize,
+
+    /// Average speed in the unit the interval was summarized in
+    pub average_speed: f64,
+
+    /// Maximum speed in the unit the interval was summarized in
+    pub max_speed: f64,
+
+    /// The unit the speeds are expressed in, e.g. `km/h` or `s/km`
+    pub unit: SpeedUnit,
+
+    /// Minimum heart rate in beats per minute
+    pub min_heart_rate: Option<f64>,
+
+    /// Average heart rate in beats per minute
+    pub average_heart_rate: Option<f64>,
+
+    /// Maximum heart rate in beats per minute
+    pub max_heart_rate: Option<f64>,
+
+    /// Total ascent in meters
+    pub elevation_gain: Option<f64>,
+
+    /// Total descent in meters
+    pub elevation_loss: Option<f64>,
+
+    /// Average cadence in steps or strokes per minute
+    pub average_cadence: Option<f64>,
+
+    /// Calories burned in kcal
+    pub calories: Option<f64>,
+
+    /// Latitude in degrees of the first record
+    pub start_latitude: Option<f64>,
+
+    /// Longitude in degrees of the first record
+    pub start_longitude: Option<f64>,
+
+    /// Latitude in degrees of the last record
+    pub end_latitude: Option<f64>,
+
+    /// Longitude in degrees of the last record
+    pub end_longitude: Option<f64>,
+
+    /// The sport the interval was recorded as
+    pub sport: Option<Sport>,
+}
+
+impl IntervalInfo {
+    /// Summarizes the records in `range`, with speeds expressed in `unit`.
+    pub fn from_range(records: &[Record], range: Range<usize>, unit: SpeedUnit) -> Self {
+        let interval = &records[range];
+        let first = &interval[0];
+        let last = &interval[interval.len() - 1];
+        let duration = last.time_in_seconds - first.time_in_seconds;
+        let distance = last.distance - first.distance;
+
+        let average_speed = if duration > 0 {
+            distance / duration as f64
+        } else {
+            first.speed.to_ms()
+        };
+        let max_speed = interval
+            .iter()
+            .map(|rec| rec.speed.to_ms())
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let heart_rates = interval.iter().filter_map(|rec| rec.heart_rate);
+        let cadences = interval.iter().filter_map(|rec| rec.cadence);
+
+        let elevations = interval
+            .iter()
+            .filter_map(|rec| rec.elevation)
+            .collect::<Vec<_>>();
+        let (elevation_gain, elevation_loss) = if elevations.is_empty() {
+            (None, None)
+        } else {
+            let deltas = elevations.windows(2).map(|pair| pair[1] - pair[0]);
+            (
+                Some(deltas.clone().filter(|delta| *delta > 0.0).sum()),
+                Some(-deltas.filter(|delta| *delta < 0.0).sum::<f64>()),
+            )
+        };
+
+        IntervalInfo {
+            start_time: first.time_in_seconds,
+            duration,
+            distance: distance.round() as usize,
+            average_speed: Speed::Ms(average_speed).to_unit(unit).value(),
+            max_speed: Speed::Ms(max_speed).to_unit(unit).value(),
+            unit,
+            min_heart_rate: heart_rates.clone().reduce(f64::min),
+            average_heart_rate: mean(heart_rates.clone()),
+            max_heart_rate: heart_rates.reduce(f64::max),
+            elevation_gain,
+            elevation_loss,
+            average_cadence: mean(cadences),
+            calories: last
+                .calories
+                .zip(first.calories)
+                .map(|(last, first)| last - first),
+            start_latitude: first.position.map(|pos| pos.latitude),
+            start_longitude: first.position.map(|pos| pos.longitude),
+            end_latitude: last.position.map(|pos| pos.latitude),
+            end_longitude: last.position.map(|pos| pos.longitude),
+            sport: first.sport,
+        }
+    }
+}
+
+impl IntervalInfo {
+    /// Number of fields written by [`serialize_fields`](Self::serialize_fields)
+    pub(crate) const FIELDS: usize = 18;
+
+    /// Writes the fields of the summary to `state`, so summaries that extend it are serialized
+    /// as a single flat row, which is required to write them as CSV.
+    pub(crate) fn serialize_fields<S: SerializeStruct>(
+        &self,
+        state: &mut S,
+    ) -> Result<(), S::Error> {
+        state.serialize_field("start_time", &self.start_time)?;
+        state.serialize_field("duration", &self.duration)?;
+        state.serialize_field("distance", &self.distance)?;
+        state.serialize_field("average_speed", &self.average_speed)?;
+        state.serialize_field("max_speed", &self.max_speed)?;
+        state.serialize_field("unit", &self.unit)?;
+        state.serialize_field("min_heart_rate", &self.min_heart_rate)?;
+        state.serialize_field("average_heart_rate", &self.average_heart_rate)?;
+        state.serialize_field("max_heart_rate", &self.max_heart_rate)?;
+        state.serialize_field("elevation_gain", &self.elevation_gain)?;
+        state.serialize_field("elevation_loss", &self.elevation_loss)?;
+        state.serialize_field("average_cadence", &self.average_cadence)?;
+        state.serialize_field("calories", &self.calories)?;
+        state.serialize_field("start_latitude", &self.start_latitude)?;
+        state.serialize_field("start_longitude", &self.start_longitude)?;
+        state.serialize_field("end_latitude", &self.end_latitude)?;
+        state.serialize_field("end_longitude", &self.end_longitude)?;
+        state.serialize_field("sport", &self.sport)?;
+        Ok(())
+    }
+}
+
+impl Serialize for IntervalInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("IntervalInfo", Self::FIELDS)?;
+        self.serialize_fields(&mut state)?;
+        state.end()
+    }
+}
+
+/// Returns the mean of `values`, or `None` if there are none.
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (count, total) = values.fold((0, 0.0), |(count, total), value| (count + 1, total + value));
+    if count > 0 {
+        Some(total / count as f64)
+    } else {
+        None
+    }
+}
+
+/// Detects intervals that are held above a certain speed or heart rate for a minimum duration.
+///
+/// Intervals never span a gap in the recording, see [`find_gaps`](crate::find_gaps).
+#[derive(Debug, Clone)]
+pub struct IntervalDetector {
+    thresholds: Thresholds,
+    unit: SpeedUnit,
+    min_duration: usize,
+    max_gap: usize,
+    grade_adjusted: bool,
+}
+
+impl IntervalDetector {
+    /// Constructs a detector that finds intervals with an average speed or heart rate of at least
+    /// `limit`.
+    pub fn new<L: Into<Limit>>(limit: L) -> Self {
+        let limit = limit.into();
+        IntervalDetector {
+            thresholds: Thresholds::new(limit),
+            unit: match limit {
+                Limit::Speed(speed) => speed.unit(),
+                Limit::HeartRate(_) => SpeedUnit::Kmph,
+            },
+            min_duration: 20,
+            max_gap: 10,
+            grade_adjusted: false,
+        }
+    }
+
+    /// Sets the minimum duration in seconds of an interval. Defaults to 20 seconds.
+    pub fn with_min_duration(mut self, min_duration: usize) -> Self {
+        self.min_duration = min_duration;
+        self
+    }
+
+    /// Sets the unit speeds of detected intervals are reported in. Defaults to the unit of the
+    /// limit, or km/h when detecting on heart rate.
+    pub fn with_unit(mut self, unit: SpeedUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Sets the limit below which the average has to drop to end an interval. Defaults to the
+    /// limit the detector was constructed with. Returns an error if the stop limit is above the
+    /// limit, see [`Thresholds::validate`].
+    pub fn try_with_stop_limit<L: Into<Limit>>(mut self, limit: L) -> Result<Self, String> {
+        self.thresholds.stop = limit.into();
+        self.thresholds.validate()?;
+        Ok(self)
+    }
+
+    /// Sets the time in seconds the average has to stay below the stop limit before an interval
+    /// ends. Defaults to 0 seconds.
+    pub fn with_stop_duration(mut self, stop_duration: usize) -> Self {
+        self.thresholds.stop_duration = stop_duration;
+        self
+    }
+
+    /// Sets the maximum time in seconds between two records before it is considered a gap in the
+    /// recording. Defaults to 10 seconds.
+    pub fn with_max_gap(mut self, max_gap: usize) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Sets whether intervals are detected on the grade-adjusted speed, see
+    /// [`grade_adjust`](crate::grade_adjust). The reported speeds are not adjusted. Defaults to
+    /// false.
+    pub fn with_grade_adjusted(mut self, grade_adjusted: bool) -> Self {
+        self.grade_adjusted = grade_adjusted;
+        self
+    }
+
+    /// Returns the index ranges of all intervals in `records`.
+    pub fn find_ranges(&self, records: &[Record]) -> Vec<Range<usize>> {
+        let adjusted;
+        let detected = if self.grade_adjusted {
+            adjusted = grade_adjust(records);
+            &adjusted
+        } else {
+            records
+        };
+
+        split_at_gaps(records, self.max_gap)
+            .into_iter()
+            .flat_map(|segment| {
+                let offset = segment.start;
+                find_all_intervals(&detected[segment], self.thresholds)
+                    .into_iter()
+                    .map(move |range| range.start + offset..range.end + offset)
+            })
+            .filter(|range| {
+                records[range.end - 1].time_in_seconds - records[range.start].time_in_seconds
+                    >= self.min_duration
+            })
+            .collect()
+    }
+
+    /// Returns a summary of all intervals in `records`.
+    pub fn detect(&self, records: &[Record]) -> Vec<IntervalInfo> {
+        self.find_ranges(records)
+            .into_iter()
+            .map(|range| IntervalInfo::from_range(records, range, self.unit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        find_interval, IntervalDetector, IntervalInfo, Limit, Position, Record, Speed, SpeedUnit,
+        Thresholds,
+    };
+
+    #[test]
+    fn test_find_interval() {
+        let records = [
+            Record::new(0, 0.0, Speed::Ms(1.0)),
+            Record::new(1, 1.0, Speed::Ms(2.0)),
+            Record::new(2, 2.0, Speed::Ms(1.8)),
+            Record::new(3, 2.0, Speed::Ms(2.2)),
+            Record::new(4, 2.0, Speed::Ms(0.0)),
+        ];
+
+        assert_eq!(find_interval(&records, 0, Speed::Ms(1.9)), Some(1..4));
+        assert_eq!(find_interval(&records, 0, Speed::Ms(2.1)), Some(3..4));
+    }
+
+    #[test]
+    fn test_find_interval_irregular_sampling() {
+        let records = [
+            Record::new(0, 0.0, Speed::Ms(3.0)),
+            Record::new(1, 3.0, Speed::Ms(3.0)),
+            Record::new(9, 11.0, Speed::Ms(1.0)),
+            Record::new(10, 14.0, Speed::Ms(3.0)),
+            Record::new(11, 14.0, Speed::Ms(0.0)),
+        ];
+
+        // The slow record covers 8 seconds so it pulls the average below the limit
+        assert_eq!(find_interval(&records, 0, Speed::Ms(2.0)), Some(0..2));
+    }
+
+    #[test]
+    fn test_find_interval_heart_rate() {
+        let mut records = (0..6)
+            .map(|time| Record::new(time, 0.0, Speed::Ms(0.0)))
+            .collect::<Vec<_>>();
+        for (rec, heart_rate) in records.iter_mut().zip(&[120.0, 170.0, 160.0, 175.0, 130.0]) {
+            rec.heart_rate = Some(*heart_rate);
+        }
+
+        assert_eq!(
+            find_interval(&records, 0, Limit::HeartRate(165.0)),
+            Some(1..4)
+        );
+    }
+
+    #[test]
+    fn test_find_interval_hysteresis() {
+        let records = [
+            Record::new(0, 0.0, Speed::Ms(1.0)),
+            Record::new(1, 1.0, Speed::Ms(4.0)),
+            Record::new(2, 2.0, Speed::Ms(2.0)),
+            Record::new(3, 2.0, Speed::Ms(1.0)),
+            Record::new(4, 2.0, Speed::Ms(4.0)),
+            Record::new(5, 2.0, Speed::Ms(4.0)),
+            Record::new(6, 2.0, Speed::Ms(0.0)),
+            Record::new(7, 2.0, Speed::Ms(0.0)),
+            Record::new(8, 2.0, Speed::Ms(0.0)),
+        ];
+
+        let mut thresholds = Thresholds::new(Speed::Ms(3.5));
+        assert_eq!(find_interval(&records, 0, thresholds), Some(1..2));
+
+        thresholds.stop = Limit::Speed(Speed::Ms(2.5));
+        assert_eq!(find_interval(&records, 0, thresholds), Some(1..3));
+
+        thresholds.stop_duration = 1;
+        assert_eq!(find_interval(&records, 0, thresholds), Some(1..7));
+
+        // A stop limit above the limit still includes the first record
+        thresholds.stop = Limit::Speed(Speed::Ms(5.0));
+        assert_eq!(find_interval(&records, 0, thresholds), Some(1..2));
+
+        let detector = IntervalDetector::new(Speed::Ms(3.5));
+        assert!(detector
+            .clone()
+            .try_with_stop_limit(Speed::Ms(5.0))
+            .is_err());
+        assert!(detector.try_with_stop_limit(Speed::Ms(2.5)).is_ok());
+    }
+
+    #[test]
+    fn test_interval_info() {
+        let mut records = (0..5)
+            .map(|time| Record::new(time, time as f64 * 4.0, Speed::Ms(4.0)))
+            .collect::<Vec<_>>();
+        records[2].speed = Speed::Ms(5.0);
+        for (rec, elevation) in records.iter_mut().zip(&[10.0, 12.0, 11.0, 11.5, 10.0]) {
+            rec.elevation = Some(*elevation);
+            rec.calories = Some(rec.time_in_seconds as f64);
+        }
+        records[1].heart_rate = Some(150.0);
+        records[3].heart_rate = Some(160.0);
+        records[4].position = Some(Position {
+            latitude: 52.0,
+            longitude: 6.0,
+        });
+
+        let info = IntervalInfo::from_range(&records, 0..5, SpeedUnit::Kmph);
+        assert_eq!(info.duration, 4);
+        assert_eq!(info.distance, 16);
+        assert!((info.average_speed - 14.4).abs() < 1e-9);
+        assert_eq!(info.max_speed, 18.0);
+        assert_eq!(info.min_heart_rate, Some(150.0));
+        assert_eq!(info.average_heart_rate, Some(155.0));
+        assert_eq!(info.max_heart_rate, Some(160.0));
+        assert_eq!(info.elevation_gain, Some(2.5));
+        assert_eq!(info.elevation_loss, Some(2.5));
+        assert_eq!(info.average_cadence, None);
+        assert_eq!(info.calories, Some(4.0));
+        assert_eq!(info.start_latitude, None);
+        assert_eq!(info.end_latitude, Some(52.0));
+    }
+
+    #[test]
+    fn test_interval_does_not_span_gap() {
+        let records = [
+            Record::new(0, 0.0, Speed::Ms(3.0)),
+            Record::new(1, 3.0, Speed::Ms(3.0)),
+            Record::new(2, 6.0, Speed::Ms(2.0)),
+            Record::new(14, 9.0, Speed::Ms(3.0)),
+            Record::new(15, 12.0, Speed::Ms(3.0)),
+            Record::new(16, 12.0, Speed::Ms(0.0)),
+            Record::new(17, 12.0, Speed::Ms(0.0)),
+            Record::new(18, 12.0, Speed::Ms(0.0)),
+        ];
+
+        let detector = IntervalDetector::new(Speed::Ms(2.5)).with_min_duration(0);
+        assert_eq!(detector.find_ranges(&records), vec![0..3, 3..5]);
+        assert_eq!(detector.with_max_gap(20).find_ranges(&records), vec![0..7]);
+    }
+
+    #[test]
+    fn test_interval_runs_to_end() {
+        // The interval is still above the limit when the recording ends
+        let records = (0..10)
+            .map(|time| Record::new(time, time as f64 * 3.0, Speed::Ms(3.0)))
+            .collect::<Vec<_>>();
+        assert_eq!(find_interval(&records, 0, Speed::Ms(2.5)), Some(0..10));
+        assert_eq!(find_interval(&records, 4, Speed::Ms(2.5)), Some(4..10));
+
+        let detector = IntervalDetector::new(Speed::Ms(2.5)).with_min_duration(0);
+        assert_eq!(detector.find_ranges(&records), vec![0..10]);
+        assert_eq!(detector.detect(&records)[0].duration, 9);
+    }
+
+    #[test]
+    fn test_interval_cut_at_gap() {
+        // A single effort above the limit throughout, of which the recording paused for 30s
+        let records = [0, 1, 2, 3, 33, 34, 35]
+            .iter()
+            .map(|&time| Record::new(time, time as f64 * 3.0, Speed::Ms(3.0)))
+            .collect::<Vec<_>>();
+
+        let detector = IntervalDetector::new(Speed::Ms(2.5)).with_min_duration(0);
+        assert_eq!(detector.find_ranges(&records), vec![0..4, 4..7]);
+        let intervals = detector.detect(&records);
+        assert_eq!(intervals[0].duration, 3);
+        assert_eq!(intervals[1].start_time, 33);
+    }
+}
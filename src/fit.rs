@@ -0,0 +1,522 @@
+//! Decoder for binary Garmin FIT activity files.
+//!
+//! Only the `record` and `lap` messages are interpreted, all other messages and developer fields
+//! are skipped. Every `lap` message in the file numbers the records from its start time onwards,
+//! starting at 1, and sets their sport.
+
+use crate::geo::TrackPoint;
+use crate::{Position, Record, Speed, Sport};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Global message number of a `lap` message
+const MESG_LAP: u16 = 19;
+
+/// Global message number of a `record` message
+const MESG_RECORD: u16 = 20;
+
+/// Field number of the timestamp field that is shared by all messages
+const FIELD_TIMESTAMP: u8 = 253;
+
+/// Seconds between the unix epoch and the FIT epoch of 31 December 1989
+const FIT_EPOCH: i64 = 631_065_600;
+
+/// Converts FIT semicircles to degrees
+const SEMICIRCLES_TO_DEGREES: f64 = 180.0 / 2_147_483_648.0;
+
+/// An error that occurred while decoding a FIT file.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    InvalidHeader,
+    UnexpectedEof,
+    UndefinedLocalMessage(u8),
+    InvalidCrc,
+    InvalidValue {
+        record: usize,
+        field: &'static str,
+        value: i64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::InvalidHeader => write!(f, "not a FIT file"),
+            Error::UnexpectedEof => write!(f, "unexpected end of file"),
+            Error::UndefinedLocalMessage(local) => {
+                write!(f, "data message for undefined local message type {}", local)
+            }
+            Error::InvalidCrc => write!(f, "checksum mismatch"),
+            Error::InvalidValue {
+                record,
+                field,
+                value,
+            } => write!(
+                f,
+                "invalid value {} for {} of record {}",
+                value, field, record
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Read all records from the FIT file at `path`.
+pub fn read_path<P: AsRef<Path>>(path: P) -> Result<Vec<Record>, Error> {
+    read_bytes(&std::fs::read(path)?)
+}
+
+/// Read all records from FIT data provided by `reader`.
+pub fn read<R: Read>(mut reader: R) -> Result<Vec<Record>, Error> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    read_bytes(&bytes)
+}
+
+/// Read all records from the contents of a FIT file.
+pub fn read_bytes(bytes: &[u8]) -> Result<Vec<Record>, Error> {
+    let header_size = *bytes.first().ok_or(Error::InvalidHeader)? as usize;
+    if header_size < 12 || bytes.len() < header_size || &bytes[8..12] != b".FIT" {
+        return Err(Error::InvalidHeader);
+    }
+    let data_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let data_end = header_size + data_size;
+    if bytes.len() < data_end + 2 {
+        return Err(Error::UnexpectedEof);
+    }
+    let expected_crc = u16::from_le_bytes([bytes[data_end], bytes[data_end + 1]]);
+    if crc(&bytes[..data_end]) != expected_crc {
+        return Err(Error::InvalidCrc);
+    }
+
+    let mut samples = Vec::new();
+    let mut lap_start_times = Vec::new();
+    for message in Decoder::new(&bytes[header_size..data_end]) {
+        let message = message?;
+        match message.global_number {
+            MESG_RECORD => samples.push(message),
+            MESG_LAP => {
+                // The timestamp of a lap is its end, so laps without a start time are skipped
+                if let Some(start_time) = message.field(2) {
+                    lap_start_times.push((start_time, message.field(25).map(sport)))
+                }
+            }
+            _ => {}
+        }
+    }
+    lap_start_times.sort_unstable_by_key(|&(start_time, _)| start_time);
+
+    to_records(&samples, &lap_start_times)
+}
+
+/// Converts the `sport` field of a `lap` message.
+fn sport(sport: i64) -> Sport {
+    match sport {
+        1 => Sport::Running,
+        2 => Sport::Cycling,
+        5 => Sport::Swimming,
+        15 => Sport::Rowing,
+        _ => Sport::Other,
+    }
+}
+
+/// Converts decoded `record` messages to records, which can't go back in time.
+fn to_records(
+    samples: &[Message],
+    lap_start_times: &[(i64, Option<Sport>)],
+) -> Result<Vec<Record>, Error> {
+    let mut records: Vec<Record> = Vec::with_capacity(samples.len());
+    let mut start_time = None;
+    let mut previous: Option<TrackPoint> = None;
+
+    for (index, sample) in samples.iter().enumerate() {
+        let time = match sample.field(FIELD_TIMESTAMP) {
+            Some(time) => time,
+            None => continue,
+        };
+        if previous.is_some_and(|previous| (time as f64) < previous.time) {
+            return Err(Error::InvalidValue {
+                record: index + 1,
+                field: "timestamp",
+                value: time,
+            });
+        }
+        let start_time = *start_time.get_or_insert(time);
+
+        let position = sample
+            .field(0)
+            .zip(sample.field(1))
+            .map(|(latitude, longitude)| Position {
+                latitude: latitude as f64 * SEMICIRCLES_TO_DEGREES,
+                longitude: longitude as f64 * SEMICIRCLES_TO_DEGREES,
+            });
+
+        let point = TrackPoint::next(
+            previous.as_ref(),
+            time as f64,
+            sample.field(5).map(|distance| distance as f64 / 100.0),
+            sample
+                .field(73)
+                .or_else(|| sample.field(6))
+                .map(|speed| speed as f64 / 1000.0),
+            position,
+        );
+        previous = Some(point);
+
+        let laps_started = lap_start_times
+            .iter()
+            .filter(|&&(lap, _)| lap <= time)
+            .count();
+
+        records.push(Record {
+            time_in_seconds: (time - start_time) as usize,
+            timestamp: Some(time + FIT_EPOCH),
+            distance: point.distance,
+            speed: Speed::Ms(point.speed),
+            position,
+            elevation: sample
+                .field(78)
+                .or_else(|| sample.field(2))
+                .map(|altitude| altitude as f64 / 5.0 - 500.0),
+            heart_rate: sample.field(3).map(|heart_rate| heart_rate as f64),
+            cadence: sample.field(4).map(|cadence| cadence as f64),
+            calories: sample.field(33).map(|calories| calories as f64),
+            lap_number: if lap_start_times.is_empty() {
+                None
+            } else {
+                Some(laps_started.max(1))
+            },
+            sport: lap_start_times
+                .get(laps_started.max(1) - 1)
+                .and_then(|&(_, sport)| sport),
+        });
+    }
+
+    Ok(records)
+}
+
+#[derive(Debug, Copy, Clone)]
+struct FieldDefinition {
+    number: u8,
+    size: usize,
+    base_type: u8,
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    big_endian: bool,
+    global_number: u16,
+    fields: Vec<FieldDefinition>,
+    developer_data_size: usize,
+}
+
+/// A decoded data message with all valid integer fields.
+#[derive(Debug)]
+struct Message {
+    global_number: u16,
+    fields: Vec<(u8, i64)>,
+}
+
+impl Message {
+    fn field(&self, number: u8) -> Option<i64> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == number)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Iterates over the data messages in the data section of a FIT file.
+struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    definitions: HashMap<u8, Definition>,
+    last_timestamp: Option<i64>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Decoder {
+            data,
+            position: 0,
+            definitions: HashMap::new(),
+            last_timestamp: None,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(Error::UnexpectedEof)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn read_definition(&mut self, local: u8, has_developer_data: bool) -> Result<(), Error> {
+        let header = self.take(5)?;
+        let big_endian = header[1] == 1;
+        let global_number = if big_endian {
+            u16::from_be_bytes([header[2], header[3]])
+        } else {
+            u16::from_le_bytes([header[2], header[3]])
+        };
+        let field_count = header[4] as usize;
+        let fields = self
+            .take(field_count * 3)?
+            .chunks(3)
+            .map(|field| FieldDefinition {
+                number: field[0],
+                size: field[1] as usize,
+                base_type: field[2],
+            })
+            .collect();
+
+        let mut developer_data_size = 0;
+        if has_developer_data {
+            let developer_field_count = self.take(1)?[0] as usize;
+            developer_data_size = self
+                .take(developer_field_count * 3)?
+                .chunks(3)
+                .map(|field| field[1] as usize)
+                .sum();
+        }
+
+        self.definitions.insert(
+            local,
+            Definition {
+                big_endian,
+                global_number,
+                fields,
+                developer_data_size,
+            },
+        );
+        Ok(())
+    }
+
+    fn read_data(&mut self, local: u8, time_offset: Option<u8>) -> Result<Message, Error> {
+        let definition = self
+            .definitions
+            .get(&local)
+            .ok_or(Error::UndefinedLocalMessage(local))?
+            .clone();
+
+        let mut fields = Vec::with_capacity(definition.fields.len());
+        for field in &definition.fields {
+            let bytes = self.take(field.size)?;
+            if let Some(value) = decode_integer(bytes, field.base_type, definition.big_endian) {
+                fields.push((field.number, value));
+            }
+        }
+        self.take(definition.developer_data_size)?;
+
+        // Compressed timestamp headers only store the lower 5 bits of the timestamp relative to
+        // the last full timestamp.
+        if let (Some(offset), Some(last)) = (time_offset, self.last_timestamp) {
+            let offset = offset as i64;
+            let mut timestamp = (last & !0x1F) + offset;
+            if offset < (last & 0x1F) {
+                timestamp += 0x20;
+            }
+            fields.retain(|(number, _)| *number != FIELD_TIMESTAMP);
+            fields.push((FIELD_TIMESTAMP, timestamp));
+        }
+
+        let message = Message {
+            global_number: definition.global_number,
+            fields,
+        };
+        if let Some(timestamp) = message.field(FIELD_TIMESTAMP) {
+            self.last_timestamp = Some(timestamp);
+        }
+        Ok(message)
+    }
+
+    fn read_message(&mut self) -> Result<Option<Message>, Error> {
+        let header = self.take(1)?[0];
+        if header & 0x80 != 0 {
+            let local = (header >> 5) & 0x03;
+            return self.read_data(local, Some(header & 0x1F)).map(Some);
+        }
+
+        let local = header & 0x0F;
+        if header & 0x40 != 0 {
+            self.read_definition(local, header & 0x20 != 0)?;
+            Ok(None)
+        } else {
+            self.read_data(local, None).map(Some)
+        }
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.position < self.data.len() {
+            match self.read_message() {
+                Ok(Some(message)) => return Some(Ok(message)),
+                Ok(None) => continue,
+                Err(err) => {
+                    self.position = self.data.len();
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Decodes a single integer field value, returns `None` for invalid values, arrays and
+/// non-integer types.
+fn decode_integer(bytes: &[u8], base_type: u8, big_endian: bool) -> Option<i64> {
+    let (size, signed, invalid): (usize, bool, u64) = match base_type & 0x1F {
+        0x00 | 0x02 => (1, false, 0xFF),
+        0x01 => (1, true, 0x7F),
+        0x0A => (1, false, 0x00),
+        0x03 => (2, true, 0x7FFF),
+        0x04 => (2, false, 0xFFFF),
+        0x0B => (2, false, 0x0000),
+        0x05 => (4, true, 0x7FFF_FFFF),
+        0x06 => (4, false, 0xFFFF_FFFF),
+        0x0C => (4, false, 0x0000_0000),
+        _ => return None,
+    };
+    if bytes.len() != size {
+        return None;
+    }
+
+    let raw = if big_endian {
+        bytes
+            .iter()
+            .fold(0u64, |acc, &byte| (acc << 8) | byte as u64)
+    } else {
+        bytes
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &byte| (acc << 8) | byte as u64)
+    };
+    if raw == invalid {
+        return None;
+    }
+
+    if signed {
+        let shift = 64 - size * 8;
+        Some(((raw << shift) as i64) >> shift)
+    } else {
+        Some(raw as i64)
+    }
+}
+
+/// Computes the FIT checksum of `bytes`.
+fn crc(bytes: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800,
+        0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+
+    bytes.iter().fold(0u16, |crc, &byte| {
+        let tmp = TABLE[(crc & 0xF) as usize];
+        let crc = ((crc >> 4) & 0x0FFF) ^ tmp ^ TABLE[(byte & 0xF) as usize];
+        let tmp = TABLE[(crc & 0xF) as usize];
+        ((crc >> 4) & 0x0FFF) ^ tmp ^ TABLE[((byte >> 4) & 0xF) as usize]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc, read_bytes, Error};
+    use crate::Sport;
+
+    /// Wraps the data section in a FIT header and trailing checksum.
+    fn fit_file(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![12, 0x10, 0x00, 0x08];
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b".FIT");
+        bytes.extend_from_slice(data);
+        let crc = crc(&bytes);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_read_fit() {
+        let mut data = Vec::new();
+
+        // Definition of a record message with timestamp, heart rate, distance and speed and a
+        // developer field.
+        data.extend_from_slice(&[0x60, 0, 0, 20, 0, 4]);
+        data.extend_from_slice(&[253, 4, 0x86, 3, 1, 0x02, 5, 4, 0x86, 6, 2, 0x84]);
+        data.extend_from_slice(&[1, 0, 1, 0]);
+
+        // Two records, the second with a compressed timestamp header
+        data.push(0x00);
+        data.extend_from_slice(&1000u32.to_le_bytes());
+        data.push(150);
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0xFFFFu16.to_le_bytes());
+        data.push(42);
+
+        data.push(0x80 | ((1000 + 4) & 0x1F) as u8);
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.push(0xFF);
+        data.extend_from_slice(&1200u32.to_le_bytes());
+        data.extend_from_slice(&3000u16.to_le_bytes());
+        data.push(42);
+
+        // A running lap starting at the first record
+        data.extend_from_slice(&[0x41, 0, 0, 19, 0, 2, 2, 4, 0x86, 25, 1, 0x00]);
+        data.push(0x01);
+        data.extend_from_slice(&1000u32.to_le_bytes());
+        data.push(1);
+
+        // A lap with only the timestamp at which it ended, which is skipped
+        data.extend_from_slice(&[0x42, 0, 0, 19, 0, 1, 253, 4, 0x86]);
+        data.push(0x02);
+        data.extend_from_slice(&1004u32.to_le_bytes());
+
+        let records = read_bytes(&fit_file(&data)).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].time_in_seconds, 0);
+        assert_eq!(records[0].heart_rate, Some(150.0));
+        assert_eq!(records[0].lap_number, Some(1));
+        assert_eq!(records[0].sport, Some(Sport::Running));
+        assert_eq!(records[1].time_in_seconds, 4);
+        assert_eq!(records[1].lap_number, Some(1));
+        assert_eq!(records[1].heart_rate, None);
+        assert_eq!(records[1].distance, 12.0);
+        assert_eq!(records[1].speed.to_ms(), 3.0);
+    }
+
+    #[test]
+    fn test_invalid_crc() {
+        let mut bytes = fit_file(&[]);
+        *bytes.last_mut().unwrap() ^= 0xFF;
+        assert!(matches!(read_bytes(&bytes), Err(Error::InvalidCrc)));
+    }
+
+    #[test]
+    fn test_decreasing_timestamp() {
+        let mut data = vec![0x40, 0, 0, 20, 0, 1, 253, 4, 0x86];
+        for time in [1000u32, 1005, 1003] {
+            data.push(0x00);
+            data.extend_from_slice(&time.to_le_bytes());
+        }
+
+        assert!(matches!(
+            read_bytes(&fit_file(&data)),
+            Err(Error::InvalidValue { record: 3, .. })
+        ));
+    }
+}
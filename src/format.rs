@@ -0,0 +1,51 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The file formats that activities can be read from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// CSV as exported by TomTom sport watches
+    TomTom,
+    /// GPX 1.1 track
+    Gpx,
+    /// Garmin Training Center XML
+    Tcx,
+    /// Binary FIT activity file
+    Fit,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Format> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" | "tomtom" => Ok(Format::TomTom),
+            "gpx" => Ok(Format::Gpx),
+            "tcx" => Ok(Format::Tcx),
+            "fit" => Ok(Format::Fit),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::TomTom => write!(f, "csv"),
+            Format::Gpx => write!(f, "gpx"),
+            Format::Tcx => write!(f, "tcx"),
+            Format::Fit => write!(f, "fit"),
+        }
+    }
+}
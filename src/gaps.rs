@@ -0,0 +1,79 @@
+use crate::Record;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A period in which the device did not record any samples, e.g. because it was paused or lost
+/// its GPS signal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gap {
+    /// Time in seconds of the last sample before the gap
+    pub start_time: usize,
+
+    /// Time in seconds between the samples around the gap
+    pub duration: usize,
+}
+
+/// Finds all places where consecutive records are more than `max_gap` seconds apart.
+pub fn find_gaps(records: &[Record], max_gap: usize) -> Vec<Gap> {
+    records
+        .windows(2)
+        .filter_map(|pair| {
+            let duration = pair[1].time_in_seconds - pair[0].time_in_seconds;
+            if duration > max_gap {
+                Some(Gap {
+                    start_time: pair[0].time_in_seconds,
+                    duration,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Splits `records` into ranges of contiguous records that do not contain a gap longer than
+/// `max_gap` seconds.
+pub fn split_at_gaps(records: &[Record], max_gap: usize) -> Vec<Range<usize>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for idx in 1..records.len() {
+        if records[idx].time_in_seconds - records[idx - 1].time_in_seconds > max_gap {
+            segments.push(start..idx);
+            start = idx;
+        }
+    }
+    if start < records.len() {
+        segments.push(start..records.len());
+    }
+    segments
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_gaps, split_at_gaps, Gap};
+    use crate::{Record, Speed};
+
+    #[test]
+    fn test_find_gaps() {
+        let records = [0, 1, 2, 30, 31, 32, 40]
+            .iter()
+            .map(|&time| Record::new(time, 0.0, Speed::Ms(0.0)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            find_gaps(&records, 5),
+            vec![
+                Gap {
+                    start_time: 2,
+                    duration: 28
+                },
+                Gap {
+                    start_time: 32,
+                    duration: 8
+                }
+            ]
+        );
+        assert_eq!(split_at_gaps(&records, 5), vec![0..3, 3..6, 6..7]);
+        assert_eq!(split_at_gaps(&records, 30), vec![0..7]);
+    }
+}
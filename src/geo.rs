@@ -0,0 +1,130 @@
+use crate::{Position, Record};
+
+/// Mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Returns the great-circle distance in meters between two positions using the haversine formula.
+pub fn haversine_distance(a: Position, b: Position) -> f64 {
+    let lat_a = a.latitude.to_radians();
+    let lat_b = b.latitude.to_radians();
+    let delta_lat = lat_b - lat_a;
+    let delta_lon = (b.longitude - a.longitude).to_radians();
+
+    let h = (delta_lat / 2.0).sin().powi(2)
+        + lat_a.cos() * lat_b.cos() * (delta_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// The time, distance and speed at a point of a track, from which the values a device didn't
+/// record at the next point are derived.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct TrackPoint {
+    /// Time in seconds
+    pub time: f64,
+
+    /// Cumulative distance in meters
+    pub distance: f64,
+
+    /// Speed in m/s
+    pub speed: f64,
+
+    pub position: Option<Position>,
+}
+
+impl TrackPoint {
+    /// Constructs the point following `previous`, using the `distance` and `speed` recorded by the
+    /// device when present.
+    ///
+    /// Without a recorded distance it is accumulated from the distance between the positions, and
+    /// without a recorded speed it is derived from the distance. When there is no time difference
+    /// there is nothing to derive a speed from, and it is assumed not to have changed.
+    pub fn next(
+        previous: Option<&TrackPoint>,
+        time: f64,
+        distance: Option<f64>,
+        speed: Option<f64>,
+        position: Option<Position>,
+    ) -> Self {
+        let distance = distance.unwrap_or_else(|| match previous {
+            Some(previous) => {
+                previous.distance
+                    + previous
+                        .position
+                        .zip(position)
+                        .map_or(0.0, |(a, b)| haversine_distance(a, b))
+            }
+            None => 0.0,
+        });
+        let speed = speed.unwrap_or_else(|| match previous {
+            Some(previous) if time > previous.time => {
+                (distance - previous.distance) / (time - previous.time)
+            }
+            Some(previous) => previous.speed,
+            None => 0.0,
+        });
+        TrackPoint {
+            time,
+            distance,
+            speed,
+            position,
+        }
+    }
+}
+
+impl From<&Record> for TrackPoint {
+    fn from(record: &Record) -> Self {
+        TrackPoint {
+            time: record.time_in_seconds as f64,
+            distance: record.distance,
+            speed: record.speed.to_ms(),
+            position: record.position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{haversine_distance, TrackPoint};
+    use crate::Position;
+
+    #[test]
+    fn test_haversine_distance() {
+        let a = Position {
+            latitude: 52.188472,
+            longitude: 5.986998,
+        };
+        let b = Position {
+            latitude: 52.188434,
+            longitude: 5.987043,
+        };
+        assert_eq!(haversine_distance(a, a), 0.0);
+        assert!((haversine_distance(a, b) - 5.22).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_next_track_point() {
+        let a = Position {
+            latitude: 52.188472,
+            longitude: 5.986998,
+        };
+        let b = Position {
+            latitude: 52.188434,
+            longitude: 5.987043,
+        };
+        let first = TrackPoint::next(None, 0.0, None, None, Some(a));
+        assert_eq!(first.distance, 0.0);
+        assert_eq!(first.speed, 0.0);
+
+        let second = TrackPoint::next(Some(&first), 2.0, None, None, Some(b));
+        assert!((second.distance - 5.22).abs() < 0.01);
+        assert!((second.speed - 2.61).abs() < 0.01);
+
+        // The recorded values are used, and the speed is kept without a time difference
+        let third = TrackPoint::next(Some(&second), 2.0, Some(10.0), None, None);
+        assert_eq!(third.distance, 10.0);
+        assert_eq!(third.speed, second.speed);
+        let fourth = TrackPoint::next(Some(&third), 3.0, None, Some(4.0), Some(b));
+        assert_eq!(fourth.distance, 10.0);
+        assert_eq!(fourth.speed, 4.0);
+    }
+}
@@ -0,0 +1,156 @@
+//! Reader for GPX 1.1 track files.
+//!
+//! GPX files only contain positions, so the cumulative distance is computed from the track
+//! points. Speed is taken from the `speed` extension when present and otherwise derived from the
+//! distance between consecutive points. Heart rate and cadence are read from the commonly used
+//! Garmin `TrackPointExtension`.
+
+use crate::geo::TrackPoint;
+use crate::xml::{child, parse_attribute, parse_point_time, parse_text};
+use crate::{Position, Record, Speed};
+use roxmltree::Node;
+use std::io::Read;
+use std::path::Path;
+
+pub use crate::xml::Error;
+
+/// Read all records from the GPX file at `path`.
+pub fn read_path<P: AsRef<Path>>(path: P) -> Result<Vec<Record>, Error> {
+    read_str(&std::fs::read_to_string(path)?)
+}
+
+/// Read all records from GPX data provided by `reader`.
+pub fn read<R: Read>(mut reader: R) -> Result<Vec<Record>, Error> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    read_str(&text)
+}
+
+/// Read all records from a GPX document.
+pub fn read_str(text: &str) -> Result<Vec<Record>, Error> {
+    let document = roxmltree::Document::parse(text)?;
+
+    let mut records: Vec<Record> = Vec::new();
+    let mut start_time = None;
+    let mut previous: Option<TrackPoint> = None;
+    let mut segment = None;
+
+    for point in document
+        .descendants()
+        .filter(|node| node.has_tag_name("trkpt"))
+    {
+        // The sport is taken from the type of the track, when it is a known sport
+        let sport = point
+            .ancestors()
+            .find(|node| node.has_tag_name("trk"))
+            .and_then(|track| child(track, "type"))
+            .and_then(|node| node.text())
+            .and_then(|text| text.parse().ok());
+
+        let position = Position {
+            latitude: parse_attribute(point, "lat")?,
+            longitude: parse_attribute(point, "lon")?,
+        };
+        let time = parse_point_time(
+            child(point, "time").ok_or(Error::MissingElement("time"))?,
+            previous.map(|previous| previous.time),
+        )?;
+        let start_time = *start_time.get_or_insert(time);
+
+        // The recording was off between track segments, so the distance doesn't cover the jump
+        if point.parent() != segment {
+            segment = point.parent();
+            if let Some(previous) = &mut previous {
+                previous.position = None;
+            }
+        }
+
+        let mut speed = None;
+
+        let mut heart_rate = None;
+        let mut cadence = None;
+        if let Some(extensions) = child(point, "extensions") {
+            for node in extensions.descendants().filter(Node::is_element) {
+                match node.tag_name().name() {
+                    "hr" | "heartrate" => heart_rate = Some(parse_text(node)?),
+                    "cad" | "cadence" => cadence = Some(parse_text(node)?),
+                    "speed" => speed = Some(parse_text(node)?),
+                    _ => {}
+                }
+            }
+        }
+
+        let track_point = TrackPoint::next(previous.as_ref(), time, None, speed, Some(position));
+        previous = Some(track_point);
+
+        records.push(Record {
+            time_in_seconds: (time - start_time).round() as usize,
+            timestamp: Some(time.round() as i64),
+            distance: track_point.distance,
+            speed: Speed::Ms(track_point.speed),
+            position: Some(position),
+            elevation: child(point, "ele").map(parse_text).transpose()?,
+            heart_rate,
+            cadence,
+            calories: None,
+            lap_number: None,
+            sport,
+        });
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_str, Error};
+
+    #[test]
+    fn test_read_gpx() {
+        let records = read_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"
+                xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+              <trk><trkseg>
+                <trkpt lat="52.188472" lon="5.986998">
+                  <ele>16.3</ele>
+                  <time>2021-09-12T08:34:47Z</time>
+                  <extensions><gpxtpx:TrackPointExtension>
+                    <gpxtpx:hr>120</gpxtpx:hr><gpxtpx:cad>80</gpxtpx:cad>
+                  </gpxtpx:TrackPointExtension></extensions>
+                </trkpt>
+                <trkpt lat="52.188434" lon="5.987043">
+                  <ele>16.2</ele>
+                  <time>2021-09-12T08:34:49Z</time>
+                </trkpt>
+              </trkseg><trkseg>
+                <trkpt lat="52.198434" lon="5.987043">
+                  <time>2021-09-12T08:44:49Z</time>
+                </trkpt>
+              </trkseg></trk>
+            </gpx>"#,
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].time_in_seconds, 0);
+        assert_eq!(records[0].heart_rate, Some(120.0));
+        assert_eq!(records[0].cadence, Some(80.0));
+        assert_eq!(records[1].time_in_seconds, 2);
+        assert_eq!(records[1].elevation, Some(16.2));
+        assert!((records[1].distance - 5.22).abs() < 0.01);
+        assert!((records[1].speed.to_ms() - 2.61).abs() < 0.01);
+        assert_eq!(records[2].distance, records[1].distance);
+    }
+
+    #[test]
+    fn test_time_going_back() {
+        let result = read_str(
+            r#"<gpx><trk><trkseg>
+                <trkpt lat="52.188472" lon="5.986998"><time>2021-09-12T08:34:49Z</time></trkpt>
+                <trkpt lat="52.188434" lon="5.987043"><time>2021-09-12T08:34:47Z</time></trkpt>
+            </trkseg></trk></gpx>"#,
+        );
+        assert!(matches!(result, Err(Error::InvalidValue { line: 3, .. })));
+    }
+}
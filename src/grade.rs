@@ -0,0 +1,93 @@
+use crate::{Record, Speed, Sport};
+
+/// Distance in meters over which the grade around a record is measured
+const GRADE_DISTANCE: f64 = 50.0;
+
+/// The steepest grade the cost model is valid for
+const MAX_GRADE: f64 = 0.45;
+
+/// Replaces the speed of `records` by the grade-adjusted speed, the speed on flat ground that
+/// takes the same effort.
+///
+/// The grade is measured from the elevation change over the surrounding 50 meters and the effort
+/// is estimated with the energy cost of running by Minetti et al. (2002). Records without an
+/// elevation, and records of another sport than running, keep their speed.
+pub fn grade_adjust(records: &[Record]) -> Vec<Record> {
+    records
+        .iter()
+        .enumerate()
+        .map(|(idx, record)| {
+            let mut record = record.clone();
+            if record.sport.is_some_and(|sport| sport != Sport::Running) {
+                return record;
+            }
+            if let Some(grade) = grade(records, idx) {
+                let factor = running_cost(grade) / running_cost(0.0);
+                record.speed = Speed::Ms(record.speed.to_ms() * factor);
+            }
+            record
+        })
+        .collect()
+}
+
+/// Returns the grade around the record at `idx` as elevation change over distance.
+fn grade(records: &[Record], idx: usize) -> Option<f64> {
+    let distance = records[idx].distance;
+    let start = records.partition_point(|rec| rec.distance < distance - GRADE_DISTANCE / 2.0);
+    let end = records.partition_point(|rec| rec.distance <= distance + GRADE_DISTANCE / 2.0);
+    let first = &records[start.min(idx)];
+    let last = &records[end.max(idx + 1) - 1];
+
+    let horizontal = last.distance - first.distance;
+    if horizontal <= 0.0 {
+        return None;
+    }
+    let rise = last.elevation? - first.elevation?;
+    Some((rise / horizontal).clamp(-MAX_GRADE, MAX_GRADE))
+}
+
+/// Energy cost of running in J/kg/m at `grade`.
+fn running_cost(grade: f64) -> f64 {
+    155.4 * grade.powi(5) - 30.4 * grade.powi(4) - 43.3 * grade.powi(3)
+        + 46.3 * grade.powi(2)
+        + 19.5 * grade
+        + 3.6
+}
+
+#[cfg(test)]
+mod test {
+    use super::grade_adjust;
+    use crate::{Record, Speed, Sport};
+
+    #[test]
+    fn test_grade_adjust() {
+        // 100 meters flat, 100 meters up at 10% and 100 meters down at 10%
+        let records = (0..=60)
+            .map(|time| {
+                let distance = time as f64 * 5.0;
+                let mut record = Record::new(time, distance, Speed::Ms(5.0));
+                record.elevation = Some(match distance {
+                    d if d <= 100.0 => 0.0,
+                    d if d <= 200.0 => (d - 100.0) * 0.1,
+                    d => 10.0 - (d - 200.0) * 0.1,
+                });
+                record
+            })
+            .collect::<Vec<_>>();
+
+        let adjusted = grade_adjust(&records);
+        assert_eq!(adjusted[5].speed.to_ms(), 5.0);
+        assert!(adjusted[30].speed.to_ms() > 7.0);
+        assert!(adjusted[50].speed.to_ms() < 4.0);
+
+        let mut cycling = records.clone();
+        cycling
+            .iter_mut()
+            .for_each(|rec| rec.sport = Some(Sport::Cycling));
+        assert_eq!(grade_adjust(&cycling)[30].speed.to_ms(), 5.0);
+
+        let mut flat = records.clone();
+        flat.iter_mut().for_each(|rec| rec.elevation = None);
+        assert_eq!(grade_adjust(&flat)[30].speed.to_ms(), 5.0);
+    }
+}
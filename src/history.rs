@@ -0,0 +1,285 @@
+//! A local history of analyzed sessions, to follow trends over time.
+//!
+//! The history is stored as a directory with a JSON file for every session, named after a hash of
+//! the full path of the file it was read from. Analyzing the same file again replaces its previous
+//! entry.
+
+use crate::output::{SessionInfo, Summary};
+use chrono::{DateTime, Datelike};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A session recorded in the history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the unix epoch at which the session took place
+    pub date: i64,
+
+    /// Label of the session, e.g. the type of workout
+    pub tag: Option<String>,
+
+    pub session: SessionInfo,
+
+    /// The intervals of the session, not including recoveries
+    pub intervals: Vec<IntervalSummary>,
+}
+
+/// The part of an interval that is kept in the history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntervalSummary {
+    /// Time in seconds since the start of the activity
+    pub start_time: usize,
+
+    /// Duration in seconds
+    pub duration: usize,
+
+    /// Distance in meters
+    pub distance: usize,
+}
+
+impl Entry {
+    /// Constructs an entry for `session` with its `intervals`.
+    pub fn new<T: Summary>(
+        date: i64,
+        tag: Option<String>,
+        session: SessionInfo,
+        intervals: &[T],
+    ) -> Self {
+        Entry {
+            date,
+            tag,
+            session,
+            intervals: intervals
+                .iter()
+                .filter(|interval| !interval.is_recovery())
+                .map(|interval| IntervalSummary {
+                    start_time: interval.start_time(),
+                    duration: interval.duration(),
+                    distance: interval.distance(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A file in the store that couldn't be read, with the reason.
+pub type InvalidFile = (PathBuf, io::Error);
+
+/// A directory with the history of analyzed sessions.
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: PathBuf,
+}
+
+impl Store {
+    /// Opens the store in `dir`, creating the directory if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Store {
+            dir: dir.as_ref().to_owned(),
+        })
+    }
+
+    /// Adds `entry` to the store, replacing an earlier entry of the same file.
+    ///
+    /// The entry is written to a temporary file first, so an interrupted write doesn't leave a
+    /// partial entry behind.
+    pub fn add(&self, entry: &Entry) -> io::Result<()> {
+        let file = Path::new(&entry.session.file);
+        let path = fs::canonicalize(file).unwrap_or_else(|_| file.to_owned());
+        let name = format!("{:016x}", fnv1a(path.to_string_lossy().as_bytes()));
+        let temp = self.dir.join(format!("{}.tmp", name));
+        let mut writer = io::BufWriter::new(fs::File::create(&temp)?);
+        serde_json::to_writer_pretty(&mut writer, entry)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(temp, self.dir.join(name + ".json"))
+    }
+
+    /// Returns all entries in the store, ordered by date, and the files that couldn't be read as
+    /// an entry with the reason.
+    pub fn entries(&self) -> io::Result<(Vec<Entry>, Vec<InvalidFile>)> {
+        let mut entries = Vec::new();
+        let mut invalid = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                let entry = fs::File::open(&path).and_then(|file| {
+                    Ok(serde_json::from_reader::<_, Entry>(io::BufReader::new(
+                        file,
+                    ))?)
+                });
+                match entry {
+                    Ok(entry) => entries.push(entry),
+                    Err(err) => invalid.push((path, err)),
+                }
+            }
+        }
+        entries.sort_by_key(|entry| entry.date);
+        Ok((entries, invalid))
+    }
+}
+
+/// 64-bit FNV-1a hash, which unlike the hasher of the standard library is stable between builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The intervals of all sessions in a single week.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekTrend {
+    /// ISO week, e.g. `2021-W07`
+    pub week: String,
+
+    /// Number of sessions
+    pub sessions: usize,
+
+    /// Number of intervals
+    pub intervals: usize,
+
+    /// Total duration of the intervals in seconds
+    pub duration: usize,
+
+    /// Total distance of the intervals in meters
+    pub distance: usize,
+
+    /// Average speed over all intervals in m/s
+    pub average_speed: f64,
+}
+
+/// Groups the intervals of `entries` per week, only including the entries with `tag` if given.
+pub fn weekly_trends(entries: &[Entry], tag: Option<&str>) -> Vec<WeekTrend> {
+    let mut trends: Vec<WeekTrend> = Vec::new();
+    for entry in entries {
+        if tag.is_some() && entry.tag.as_deref() != tag {
+            continue;
+        }
+        let week = match DateTime::from_timestamp(entry.date, 0) {
+            Some(date) => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            None => continue,
+        };
+
+        let index = match trends.iter().position(|trend| trend.week == week) {
+            Some(index) => index,
+            None => {
+                trends.push(WeekTrend {
+                    week,
+                    sessions: 0,
+                    intervals: 0,
+                    duration: 0,
+                    distance: 0,
+                    average_speed: 0.0,
+                });
+                trends.len() - 1
+            }
+        };
+        let trend = &mut trends[index];
+        trend.sessions += 1;
+        trend.intervals += entry.intervals.len();
+        trend.duration += entry.intervals.iter().map(|i| i.duration).sum::<usize>();
+        trend.distance += entry.intervals.iter().map(|i| i.distance).sum::<usize>();
+    }
+
+    for trend in &mut trends {
+        if trend.duration > 0 {
+            trend.average_speed = trend.distance as f64 / trend.duration as f64;
+        }
+    }
+    trends.sort_by(|a, b| a.week.cmp(&b.week));
+    trends
+}
+
+#[cfg(test)]
+mod test {
+    use super::{weekly_trends, Entry, IntervalSummary, Store};
+    use crate::output::SessionInfo;
+
+    fn entry(date: i64, tag: &str, durations: &[usize]) -> Entry {
+        Entry {
+            date,
+            tag: Some(tag.to_owned()),
+            session: SessionInfo {
+                file: format!("{}.csv", date),
+                format: "csv".to_owned(),
+                start_date: None,
+                records: 100,
+                duration: 99,
+                distance: 300,
+                limit: None,
+                gaps: Vec::new(),
+                corrected_samples: 0,
+            },
+            intervals: durations
+                .iter()
+                .map(|&duration| IntervalSummary {
+                    start_time: 0,
+                    duration,
+                    distance: duration * 4,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_weekly_trends() {
+        // Monday 1 and Sunday 7 February 2021, followed by Monday 8 February
+        let entries = [
+            entry(1_612_137_600, "tempo", &[100, 200]),
+            entry(1_612_656_000, "tempo", &[100]),
+            entry(1_612_656_000, "hills", &[50]),
+            entry(1_612_742_400, "tempo", &[200]),
+        ];
+
+        let trends = weekly_trends(&entries, Some("tempo"));
+        assert_eq!(trends.len(), 2);
+        assert_eq!(trends[0].week, "2021-W05");
+        assert_eq!(trends[0].sessions, 2);
+        assert_eq!(trends[0].intervals, 3);
+        assert_eq!(trends[0].duration, 400);
+        assert_eq!(trends[0].average_speed, 4.0);
+        assert_eq!(trends[1].week, "2021-W06");
+
+        assert_eq!(weekly_trends(&entries, None)[0].sessions, 3);
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = std::env::temp_dir().join(format!("interval_history_{}", std::process::id()));
+        let store = Store::open(&dir).unwrap();
+        store.add(&entry(1_612_656_000, "tempo", &[100])).unwrap();
+        store.add(&entry(1_612_137_600, "tempo", &[200])).unwrap();
+        store.add(&entry(1_612_137_600, "tempo", &[300])).unwrap();
+
+        // Files with the same name in different directories are different sessions
+        for file in [
+            "jan/run.csv",
+            "feb/run.csv",
+            "feb/run_1.csv",
+            "feb/run/1.csv",
+        ] {
+            let mut entry = entry(1_612_742_400, "tempo", &[100]);
+            entry.session.file = file.to_owned();
+            store.add(&entry).unwrap();
+        }
+
+        std::fs::write(dir.join("corrupt.json"), "{").unwrap();
+
+        let (entries, invalid) = store.entries().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(invalid.len(), 1);
+        assert!(invalid[0].0.ends_with("corrupt.json"));
+        assert_eq!(entries[0].date, 1_612_137_600);
+        assert_eq!(entries[0].intervals[0].duration, 300);
+    }
+}
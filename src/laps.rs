@@ -0,0 +1,132 @@
+use crate::Record;
+use serde::Serialize;
+use std::ops::Range;
+
+/// Returns the laps the athlete marked on the device together with their lap number.
+///
+/// A lap runs from its first record to the first record of the next lap, so the laps cover the
+/// whole session. Records without a lap number are not part of any lap.
+pub fn lap_ranges(records: &[Record]) -> Vec<(usize, Range<usize>)> {
+    let mut laps: Vec<(usize, Range<usize>)> = Vec::new();
+    for (idx, record) in records.iter().enumerate() {
+        let lap = match record.lap_number {
+            Some(lap) => lap,
+            None => continue,
+        };
+        match laps.last_mut() {
+            Some((number, range)) if *number == lap && range.end == idx => range.end = idx + 1,
+            Some((_, range)) if range.end == idx => {
+                range.end = idx + 1;
+                laps.push((lap, idx..idx + 1));
+            }
+            _ => laps.push((lap, idx..idx + 1)),
+        }
+    }
+    laps
+}
+
+/// How a detected interval overlaps with one of the laps of the device.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LapOverlap {
+    /// Number of the detected interval, starting at 1
+    pub interval: usize,
+
+    /// Number of the lap on the device
+    pub lap: usize,
+
+    /// Time in seconds since the start of the activity at which the overlap starts
+    pub start_time: usize,
+
+    /// Duration of the overlap in seconds
+    pub duration: usize,
+
+    /// Distance of the overlap in meters
+    pub distance: usize,
+
+    /// Part of the interval that is covered by the lap, between 0 and 1
+    pub interval_coverage: f64,
+
+    /// Part of the lap that is covered by the interval, between 0 and 1
+    pub lap_coverage: f64,
+}
+
+/// Compares the detected `intervals` with the `laps` of the device, returning every overlap
+/// between an interval and a lap in order.
+pub fn compare_laps(
+    records: &[Record],
+    intervals: &[Range<usize>],
+    laps: &[(usize, Range<usize>)],
+) -> Vec<LapOverlap> {
+    let duration = |range: &Range<usize>| {
+        records[range.end - 1].time_in_seconds - records[range.start].time_in_seconds
+    };
+    let coverage = |overlap: usize, total: usize| {
+        if total > 0 {
+            overlap as f64 / total as f64
+        } else {
+            1.0
+        }
+    };
+
+    let mut overlaps = Vec::new();
+    for (idx, interval) in intervals.iter().enumerate() {
+        for (lap, range) in laps {
+            let overlap = interval.start.max(range.start)..interval.end.min(range.end);
+            if overlap.start >= overlap.end {
+                continue;
+            }
+            // Laps share their boundary record, only touching it is not an overlap
+            let overlap_duration = duration(&overlap);
+            if overlap_duration == 0 && duration(interval) > 0 {
+                continue;
+            }
+
+            overlaps.push(LapOverlap {
+                interval: idx + 1,
+                lap: *lap,
+                start_time: records[overlap.start].time_in_seconds,
+                duration: overlap_duration,
+                distance: (records[overlap.end - 1].distance - records[overlap.start].distance)
+                    .round() as usize,
+                interval_coverage: coverage(overlap_duration, duration(interval)),
+                lap_coverage: coverage(overlap_duration, duration(range)),
+            });
+        }
+    }
+    overlaps
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compare_laps, lap_ranges};
+    use crate::{Record, Speed};
+
+    #[test]
+    fn test_laps() {
+        let records = (0..10)
+            .map(|time| {
+                let mut record = Record::new(time, time as f64 * 3.0, Speed::Ms(3.0));
+                record.lap_number = Some(if time < 4 { 1 } else { 2 });
+                record
+            })
+            .collect::<Vec<_>>();
+
+        let laps = lap_ranges(&records);
+        assert_eq!(laps, vec![(1, 0..5), (2, 4..10)]);
+
+        let overlaps = compare_laps(&records, &[2..8, 8..10], &laps);
+        assert_eq!(overlaps.len(), 3);
+        assert_eq!(overlaps[0].lap, 1);
+        assert_eq!(overlaps[0].start_time, 2);
+        assert_eq!(overlaps[0].duration, 2);
+        assert_eq!(overlaps[0].distance, 6);
+        assert_eq!(overlaps[0].interval_coverage, 0.4);
+        assert_eq!(overlaps[0].lap_coverage, 0.5);
+        assert_eq!(overlaps[1].lap, 2);
+        assert_eq!(overlaps[1].duration, 3);
+        assert_eq!(overlaps[2].interval, 2);
+        assert_eq!(overlaps[2].lap, 2);
+
+        assert_eq!(compare_laps(&records, &[5..8, 8..10], &laps).len(), 2);
+    }
+}
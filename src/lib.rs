@@ -0,0 +1,53 @@
+//! Detect intervals with an average pace from GPS activity files.
+//!
+//! Activities can be read from TomTom CSV exports ([`tomtom`]), GPX tracks ([`gpx`]), Garmin
+//! TCX files ([`tcx`]) and binary FIT files ([`fit`]).
+//!
+//! ```no_run
+//! use interval_detector::{tomtom, IntervalDetector, Speed};
+//!
+//! let records = tomtom::read_path("activity.csv").unwrap();
+//! let intervals = IntervalDetector::new(Speed::Kmph(15.0))
+//!     .with_min_duration(30)
+//!     .detect(&records);
+//! ```
+
+mod auto;
+mod detector;
+pub mod fit;
+mod format;
+mod gaps;
+mod geo;
+pub mod gpx;
+mod grade;
+pub mod history;
+mod laps;
+mod limit;
+mod outlier;
+pub mod output;
+mod record;
+mod resample;
+mod segment;
+mod smooth;
+mod speed;
+mod sport;
+pub mod tcx;
+pub mod tomtom;
+pub mod workout;
+mod xml;
+
+pub use auto::auto_limit;
+pub use detector::{find_all_intervals, find_interval, IntervalDetector, IntervalInfo};
+pub use format::Format;
+pub use gaps::{find_gaps, split_at_gaps, Gap};
+pub use geo::haversine_distance;
+pub use grade::grade_adjust;
+pub use laps::{compare_laps, lap_ranges, LapOverlap};
+pub use limit::{Limit, Thresholds};
+pub use outlier::reject_outliers;
+pub use record::{Position, Record};
+pub use resample::resample;
+pub use segment::{segments, Segment, SegmentKind};
+pub use smooth::{smooth, Filter};
+pub use speed::{Speed, SpeedUnit};
+pub use sport::{split_by_sport, Sport};
@@ -0,0 +1,144 @@
+use crate::{Record, Speed};
+use std::fmt;
+use std::str::FromStr;
+
+/// The measurement an interval is detected on and the value it has to stay above.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Limit {
+    /// Detect intervals on the speed of the records
+    Speed(Speed),
+    /// Detect intervals on the heart rate of the records in beats per minute
+    HeartRate(f64),
+}
+
+impl Limit {
+    /// Returns the limit in the unit returned by [`Limit::value`].
+    pub fn threshold(self) -> f64 {
+        match self {
+            Limit::Speed(speed) => speed.to_ms(),
+            Limit::HeartRate(heart_rate) => heart_rate,
+        }
+    }
+
+    /// Returns the measurement of `record` this limit applies to, or `None` if it wasn't recorded.
+    pub fn value(self, record: &Record) -> Option<f64> {
+        match self {
+            Limit::Speed(_) => Some(record.speed.to_ms()),
+            Limit::HeartRate(_) => record.heart_rate,
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Speed(speed) => write!(f, "{}", speed),
+            Limit::HeartRate(heart_rate) => write!(f, "{} bpm", heart_rate),
+        }
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    /// Parses a heart rate like `165bpm` or any speed that [`Speed`] can parse.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.strip_suffix("bpm") {
+            Some(heart_rate) => heart_rate
+                .trim()
+                .parse()
+                .map(Limit::HeartRate)
+                .map_err(|_| format!("invalid heart rate '{}'", s)),
+            None => s.parse().map(Limit::Speed),
+        }
+    }
+}
+
+impl From<Speed> for Limit {
+    fn from(speed: Speed) -> Self {
+        Limit::Speed(speed)
+    }
+}
+
+/// The limits that start and end an interval.
+///
+/// Using a lower `stop` than `start` limit makes detection less sensitive to the exact limit as
+/// an interval only ends once the effort clearly dropped.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Thresholds {
+    /// An interval starts at the first record at or above this limit
+    pub start: Limit,
+
+    /// An interval ends when the average since its start drops below this limit
+    pub stop: Limit,
+
+    /// The time in seconds the average has to stay below `stop` before the interval ends
+    pub stop_duration: usize,
+}
+
+impl Thresholds {
+    /// Constructs thresholds that start and stop an interval at the same `limit`.
+    pub fn new<L: Into<Limit>>(limit: L) -> Self {
+        let limit = limit.into();
+        Thresholds {
+            start: limit,
+            stop: limit,
+            stop_duration: 0,
+        }
+    }
+
+    /// Checks that the stop limit measures the same as the start limit and is not above it, as
+    /// an interval would otherwise end before it started.
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.start, self.stop) {
+            (Limit::Speed(_), Limit::Speed(_)) | (Limit::HeartRate(_), Limit::HeartRate(_)) => {}
+            _ => {
+                return Err(format!(
+                    "the stop limit {} does not measure the same as the limit {}",
+                    self.stop, self.start
+                ))
+            }
+        }
+        if self.stop.threshold() > self.start.threshold() {
+            return Err(format!(
+                "the stop limit {} is above the limit {}",
+                self.stop, self.start
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<Limit> for Thresholds {
+    fn from(limit: Limit) -> Self {
+        Thresholds::new(limit)
+    }
+}
+
+impl From<Speed> for Thresholds {
+    fn from(speed: Speed) -> Self {
+        Thresholds::new(speed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Limit, Thresholds};
+    use crate::Speed;
+
+    #[test]
+    fn test_validate_thresholds() {
+        let mut thresholds = Thresholds::new(Speed::SecPerKm(270.0));
+        assert!(thresholds.validate().is_ok());
+
+        thresholds.stop = Limit::Speed(Speed::SecPerKm(300.0));
+        assert!(thresholds.validate().is_ok());
+
+        thresholds.stop = Limit::Speed(Speed::SecPerKm(260.0));
+        assert!(thresholds.validate().is_err());
+
+        thresholds.stop = Limit::HeartRate(150.0);
+        assert!(thresholds.validate().is_err());
+    }
+}
@@ -1,54 +1,40 @@
-use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
+use glob::Pattern;
+use interval_detector::history::{weekly_trends, Entry, Store};
+use interval_detector::output::{self, OutputFormat, SessionInfo};
+use interval_detector::output::{FileSummary, Summary};
+use interval_detector::workout::{match_workout, rep_infos, Workout};
+use interval_detector::{
+    auto_limit, compare_laps, find_gaps, fit, gpx, lap_ranges, reject_outliers, resample, segments,
+    smooth, split_by_sport, tcx, tomtom, Filter, Format, IntervalDetector, IntervalInfo, Limit,
+    Record, Speed, SpeedUnit, Sport, Thresholds,
+};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
 use std::ops::Range;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+use structopt::clap::{self, AppSettings};
 use structopt::StructOpt;
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-enum Speed {
-    Kmph(f64),
-    Ms(f64),
-    SecPer500m(f64),
-}
-
-impl PartialOrd for Speed {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.to_ms().partial_cmp(&other.to_ms())
-    }
-}
-
-impl Speed {
-    /// Convert the speed to a meters per second
-    fn to_ms(self) -> f64 {
-        match self {
-            Speed::Kmph(kmph) => kmph / 3.6,
-            Speed::Ms(ms) => ms,
-            Speed::SecPer500m(pace) => 500.0 / pace,
-        }
-    }
-
-    // /// Convert the speed to kilometers per hour
-    // fn to_kmph(self) -> f64 {
-    //     match self {
-    //         Speed::Kmph(kmph) => kmph,
-    //         Speed::Ms(ms) => ms * 3.6,
-    //         Speed::SecPer500m(pace) => 500.0 / pace * 3.6,
-    //     }
-    // }
-    //
-    // /// Convert the speed to pace
-    // fn to_pace(self) -> f64 {
-    //     match self {
-    //         Speed::Kmph(kmph) => 500.0 * 3.6 / kmph,
-    //         Speed::Ms(ms) => 500.0 / ms,
-    //         Speed::SecPer500m(pace) => pace,
-    //     }
-    // }
-}
+use walkdir::WalkDir;
 
 #[derive(Debug, StructOpt)]
-#[structopt(name = "interval_detector", about = "Find intervals from CSV files")]
+#[structopt(
+    name = "interval_detector",
+    about = "Find intervals from CSV, GPX, TCX or FIT files",
+    setting = AppSettings::ArgsNegateSubcommands,
+    setting = AppSettings::SubcommandsNegateReqs
+)]
 struct Opt {
+    /// The average speed or heart rate of an interval, e.g. "4:30/km", "1:45/500m", "15 km/h",
+    /// "10 mph", "1:30/100m", "8 kn" or "165 bpm"
+    #[structopt(long, short = "l")]
+    limit: Option<Limit>,
+
     /// The average speed in Km/hour of an interval
     #[structopt(long, short = "k")]
     limit_kmph: Option<f64>,
@@ -57,194 +43,802 @@ struct Opt {
     #[structopt(long, short = "p")]
     limit_pace: Option<f64>,
 
+    /// The average heart rate in beats per minute of an interval
+    #[structopt(long)]
+    limit_hr: Option<f64>,
+
+    /// The limit for the parts of a multisport session of one sport, e.g. "running=4:30/km" or
+    /// "cycling=30 km/h". Parts without a limit of their own use the limit above
+    #[structopt(long, number_of_values = 1)]
+    sport_limit: Vec<SportLimit>,
+
+    /// Derive the speed limit from the speed distribution of the session
+    #[structopt(long)]
+    auto: bool,
+
+    /// Match the session against a planned workout, e.g. "8 x 400m / 90s" or "3 x (1000m, 500m)"
+    #[structopt(long, conflicts_with_all = &["laps", "compare-laps", "recoveries"])]
+    workout: Option<Workout>,
+
+    /// The average an interval has to drop below to end, e.g. "4:50/km", or "4:50" in the unit
+    /// of the limit. Can't be above the limit and defaults to the limit itself. Only applies to a
+    /// limit that is given, not to automatic or per sport limits
+    #[structopt(long, conflicts_with_all = &["auto", "sport-limit"])]
+    stop_limit: Option<String>,
+
+    /// The time in seconds the average has to stay below the stop limit to end an interval, which
+    /// is the limit itself when no stop limit is given
+    #[structopt(long, default_value = "0")]
+    stop_duration: usize,
+
     /// The minimum duration of an interval
     #[structopt(short, long, default_value = "20")]
     min_interval_duration: usize,
 
-    /// Input file
-    #[structopt(parse(from_os_str))]
-    input: PathBuf,
-}
+    /// Report the laps marked on the device instead of detecting intervals
+    #[structopt(long, conflicts_with_all = &["compare-laps", "recoveries"])]
+    laps: bool,
+
+    /// Compare the detected intervals with the laps marked on the device
+    #[structopt(long, conflicts_with = "recoveries")]
+    compare_laps: bool,
+
+    /// Also report the recovery periods between the intervals
+    #[structopt(long)]
+    recoveries: bool,
+
+    /// The maximum time in seconds between two samples before it is considered a gap
+    #[structopt(long, default_value = "10")]
+    max_gap: usize,
 
-#[derive(Debug, Deserialize)]
-struct RawRecord {
-    #[serde(rename(deserialize = "time"))]
-    time_in_seconds: usize,
+    /// Resample the records to a fixed interval in seconds before detecting intervals
+    #[structopt(long, parse(try_from_str = parse_resample))]
+    resample: Option<NonZeroUsize>,
 
-    #[serde(rename(deserialize = "activityType"))]
-    activity_type: isize,
+    /// Replace samples with an implausible speed, acceleration or position jump by interpolation
+    #[structopt(long)]
+    reject_outliers: bool,
 
-    #[serde(rename(deserialize = "lapNumber"))]
-    lap_number: Option<usize>,
+    /// The highest plausible speed when rejecting outliers
+    #[structopt(long, default_value = "20 m/s")]
+    max_speed: Speed,
 
-    distance: Option<f64>,
+    /// The highest plausible acceleration in m/s² when rejecting outliers
+    #[structopt(long, default_value = "10")]
+    max_acceleration: f64,
 
-    speed: Option<f64>,
+    /// Detect intervals on the grade-adjusted speed derived from the elevation, for running only
+    #[structopt(long)]
+    grade_adjusted: bool,
 
-    calories: Option<usize>,
+    /// Smooth the speed before detecting intervals with moving-average, exponential, median or
+    /// kalman, optionally followed by the window or strength, e.g. "median:5" or "exponential:3"
+    #[structopt(long)]
+    smooth: Option<Filter>,
 
-    #[serde(rename(deserialize = "lat"))]
-    latitide: Option<f64>,
+    /// The format to write the results in (csv, json, ndjson or table)
+    #[structopt(long, default_value = "csv")]
+    output_format: OutputFormat,
 
-    #[serde(rename(deserialize = "long"))]
-    longtitude: Option<f64>,
+    /// The format of the input file (csv, gpx, tcx or fit), by default derived from its extension
+    #[structopt(long)]
+    format: Option<Format>,
 
-    elevation: Option<f64>,
+    /// Search the input directories recursively
+    #[structopt(short, long)]
+    recursive: bool,
 
-    #[serde(rename(deserialize = "heartRate"))]
-    heart_rate: Option<String>,
-    cycles: Option<usize>,
+    /// Only read the files in input directories of which the name or the path within the
+    /// directory matches one of these glob patterns, e.g. "2021-*.fit" or "2021/*.fit". Defaults
+    /// to all files in a supported format
+    #[structopt(long, number_of_values = 1)]
+    include: Vec<Pattern>,
+
+    /// Record the detected intervals of the analyzed sessions in the history in this directory
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["laps", "compare-laps", "workout"])]
+    store: Option<PathBuf>,
+
+    /// Label the sessions recorded in the history, e.g. with the type of workout
+    #[structopt(long)]
+    tag: Option<String>,
+
+    /// Input files or directories
+    #[structopt(parse(from_os_str), required = true)]
+    inputs: Vec<PathBuf>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
+/// A limit for the parts of a multisport session of a single sport.
 #[derive(Debug)]
-struct Record {
-    time_in_seconds: usize,
-    distance: f64,
-    speed: Speed,
+struct SportLimit {
+    sport: Sport,
+    limit: Limit,
 }
 
-fn find_interval(records: &[Record], start_index: usize, limit: Speed) -> Option<Range<usize>> {
-    let start_index = records
-        .iter()
-        .enumerate()
-        .skip(start_index)
-        .find_map(|(idx, rec)| if rec.speed >= limit { Some(idx) } else { None })?;
+impl FromStr for SportLimit {
+    type Err = String;
 
-    let mut total_speed = 0.0;
-    for (idx, rec) in records.iter().enumerate().skip(start_index) {
-        total_speed += rec.speed.to_ms();
-        let average_speed_ms = total_speed / (idx - start_index + 1) as f64;
-        if Speed::Ms(average_speed_ms) < limit {
-            return Some(start_index..idx);
-        }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sport, limit) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <sport>=<limit>, got '{}'", s))?;
+        Ok(SportLimit {
+            sport: sport.parse()?,
+            limit: limit.parse()?,
+        })
     }
+}
 
-    None
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Show the weekly trends of the sessions in the history
+    History {
+        /// The directory of the history
+        #[structopt(long, parse(from_os_str))]
+        store: PathBuf,
+
+        /// Only include the sessions with this tag
+        #[structopt(long)]
+        tag: Option<String>,
+
+        /// The unit paces are shown in
+        #[structopt(long, default_value = "km/h")]
+        unit: SpeedUnit,
+
+        /// The format to write the trends in (csv, json, ndjson or table)
+        #[structopt(long, default_value = "table")]
+        output_format: OutputFormat,
+    },
 }
 
-fn find_all_intervals(records: &[Record], limit: Speed) -> Vec<Range<usize>> {
-    let mut results = Vec::new();
-    let mut start_index = 0;
-    loop {
-        match find_interval(records, start_index, limit) {
-            Some(interval) => {
-                start_index = interval.end;
-                results.push(interval);
-            }
-            None => {
-                return results;
+/// An error that ends the program, each kind with its own exit code.
+#[derive(Debug)]
+enum Error {
+    /// Invalid or missing command line arguments
+    Usage(String),
+
+    /// A file could not be opened, read or written
+    Io(String),
+
+    /// An input file is not in a supported format or contains invalid values
+    InvalidInput(String),
+
+    /// The session does not contain what the analysis needs
+    Analysis(String),
+
+    /// The results could not be written
+    Output(io::Error),
+
+    /// Some of the files in batch mode could not be analyzed
+    Batch {
+        failed: usize,
+        total: usize,
+        first: Box<Error>,
+    },
+}
+
+impl Error {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::Io(_) => 3,
+            Error::InvalidInput(_) => 4,
+            Error::Analysis(_) => 5,
+            Error::Output(_) => 6,
+            Error::Batch { first, .. } => first.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(message)
+            | Error::Io(message)
+            | Error::InvalidInput(message)
+            | Error::Analysis(message) => write!(f, "{}", message),
+            Error::Output(err) => write!(f, "could not write output: {}", err),
+            Error::Batch { failed, total, .. } => {
+                write!(f, "{} of {} files could not be analyzed", failed, total)
             }
         }
     }
 }
 
-#[derive(Debug, Serialize)]
-struct IntervalInfo {
-    start_time: usize,
-    duration: usize,
-    distance: usize,
+impl From<tomtom::Error> for Error {
+    fn from(err: tomtom::Error) -> Self {
+        match err {
+            tomtom::Error::Io(err) => Error::Io(format!("could not open input file: {}", err)),
+            err => Error::InvalidInput(format!("invalid input file: {}", err)),
+        }
+    }
+}
+
+impl From<gpx::Error> for Error {
+    fn from(err: gpx::Error) -> Self {
+        match err {
+            gpx::Error::Io(err) => Error::Io(format!("could not open input file: {}", err)),
+            err => Error::InvalidInput(format!("invalid input file: {}", err)),
+        }
+    }
+}
+
+impl From<fit::Error> for Error {
+    fn from(err: fit::Error) -> Self {
+        match err {
+            fit::Error::Io(err) => Error::Io(format!("could not open input file: {}", err)),
+            err => Error::InvalidInput(format!("invalid input file: {}", err)),
+        }
+    }
+}
+
+/// Converts the result of writing the output, where stdout being closed early, e.g. by piping
+/// into `head`, is not an error.
+fn output_result(result: io::Result<()>) -> Result<(), Error> {
+    match result {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(Error::Output(err)),
+        _ => Ok(()),
+    }
 }
 
 fn main() {
-    let args = Opt::from_args();
-
-    let limit = if !(args.limit_kmph.is_some() ^ args.limit_pace.is_some()) {
-        println!("error: must specify either --limit-kmph or --limit-pace");
-        return;
-    } else if let Some(limit) = args.limit_kmph {
-        Speed::Kmph(limit)
-    } else if let Some(limit) = args.limit_pace {
-        Speed::SecPer500m(limit)
-    } else {
-        unreachable!()
+    let args = match Opt::from_iter_safe(std::env::args_os()) {
+        Ok(args) => args,
+        Err(err) => match err.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => err.exit(),
+            _ => {
+                eprintln!("{}", err.message);
+                process::exit(2);
+            }
+        },
     };
 
-    // Iterate over all records
-    let mut records: Vec<RawRecord> = csv::Reader::from_path(&args.input)
-        .expect("could not open input file")
-        .into_deserialize()
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
-
-    // If the last field has activityType=-1, remove it
-    if matches!(
-        records.last(),
-        Some(RawRecord {
-            activity_type: -1,
-            ..
-        })
-    ) {
-        records.pop();
+    if let Err(err) = try_main(&args) {
+        eprintln!("error: {}", err);
+        process::exit(err.exit_code());
     }
+}
 
-    // TODO: Find gaps in the timeline
+fn try_main(args: &Opt) -> Result<(), Error> {
+    if let Some(Command::History {
+        store,
+        tag,
+        unit,
+        output_format,
+    }) = &args.command
+    {
+        let (entries, invalid) = Store::open(store)
+            .and_then(|store| store.entries())
+            .map_err(|err| Error::Io(format!("could not read history: {}", err)))?;
+        for (path, err) in invalid {
+            eprintln!("skipping {}: {}", path.display(), err);
+        }
+        let trends = weekly_trends(&entries, tag.as_deref());
+        return output_result(output::write_trends(
+            io::stdout(),
+            *output_format,
+            &trends,
+            *unit,
+        ));
+    }
 
-    // Convert to something we can work with
-    let records = records
-        .into_iter()
-        .map(|raw| Record {
-            time_in_seconds: raw.time_in_seconds,
-            distance: raw.distance.unwrap(),
-            speed: Speed::Ms(raw.speed.unwrap()),
-        })
-        .collect::<Vec<_>>();
+    let limits = [
+        args.limit,
+        args.limit_kmph
+            .map(|limit| Limit::Speed(Speed::Kmph(limit))),
+        args.limit_pace
+            .map(|limit| Limit::Speed(Speed::SecPer500m(limit))),
+        args.limit_hr.map(Limit::HeartRate),
+    ];
+    let given = limits.iter().flatten().collect::<Vec<_>>();
+    let limit = match (given.as_slice(), args.auto) {
+        ([limit], false) => {
+            let stop_limit = match &args.stop_limit {
+                None => **limit,
+                Some(stop_limit) => parse_stop_limit(stop_limit, **limit)
+                    .map_err(|err| Error::Usage(format!("invalid --stop-limit: {}", err)))?,
+            };
+            let thresholds = Thresholds {
+                start: **limit,
+                stop: stop_limit,
+                stop_duration: args.stop_duration,
+            };
+            thresholds
+                .validate()
+                .map_err(|err| Error::Usage(format!("invalid --stop-limit: {}", err)))?;
+            Some((**limit, stop_limit))
+        }
+        ([], true) => None,
+        ([], false) if args.workout.is_some() || args.laps || !args.sport_limit.is_empty() => None,
+        ([], false) => {
+            return Err(Error::Usage(
+                "must specify one of --limit, --limit-kmph, --limit-pace, --limit-hr, --auto or \
+                 --workout"
+                    .to_owned(),
+            ));
+        }
+        _ => {
+            return Err(Error::Usage(
+                "only one of --limit, --limit-kmph, --limit-pace, --limit-hr and --auto can be \
+                 given"
+                    .to_owned(),
+            ));
+        }
+    };
+
+    let inputs = find_inputs(args).map_err(|err| Error::Io(err.to_string()))?;
+    if inputs.is_empty() {
+        return Err(Error::Usage("no input files found".to_owned()));
+    }
 
-    let intervals = find_all_intervals(&records, limit)
-        .into_iter()
-        .filter(|range| {
-            records[range.end - 1].time_in_seconds - records[range.start].time_in_seconds
-                >= args.min_interval_duration
+    if let Some(workout) = &args.workout {
+        run(args, &inputs, |records, _| {
+            let ranges = match_workout(records, workout).ok_or_else(|| {
+                Error::Analysis("the session is too short for the workout".to_owned())
+            })?;
+            // Reps are shown in the unit of the limit or the sport, rowing when neither is known
+            let unit = session_unit(limit, records).unwrap_or(SpeedUnit::SecPer500m);
+            Ok((rep_infos(records, workout, &ranges, unit), unit))
+        })
+    } else if args.laps {
+        run(args, &inputs, |records, _| {
+            // The laps of a multisport session are shown in the unit of their sport
+            let multisport = is_multisport(records);
+            let unit = session_unit(limit, records).unwrap_or(SpeedUnit::Kmph);
+            let laps = device_laps(records)?
+                .into_iter()
+                .map(|(_, range)| {
+                    let lap_unit = match records[range.start].sport {
+                        Some(sport) if multisport => sport.unit(),
+                        _ => unit,
+                    };
+                    IntervalInfo::from_range(records, range, lap_unit)
+                })
+                .collect();
+            Ok((laps, unit))
+        })
+    } else if args.compare_laps {
+        run(args, &inputs, |records, session| {
+            let laps = device_laps(records)?;
+            let detectors = sport_detectors(args, limit, records, session)?;
+            let ranges = detectors
+                .iter()
+                .flat_map(|(part, detector, _)| {
+                    let offset = part.start;
+                    detector
+                        .find_ranges(&records[part.clone()])
+                        .into_iter()
+                        .map(move |range| range.start + offset..range.end + offset)
+                })
+                .collect::<Vec<_>>();
+            Ok((
+                compare_laps(records, &ranges, &laps),
+                table_unit(&detectors),
+            ))
         })
-        .map(|range| IntervalInfo {
-            start_time: records[range.start].time_in_seconds,
-            duration: records[range.end - 1].time_in_seconds - records[range.start].time_in_seconds,
-            distance: (records[range.end - 1].distance - records[range.start].distance).round()
-                as usize,
+    } else if args.recoveries {
+        run(args, &inputs, |records, session| {
+            let detectors = sport_detectors(args, limit, records, session)?;
+            let segments = detectors
+                .iter()
+                .flat_map(|(part, detector, unit)| {
+                    let part = &records[part.clone()];
+                    segments(part, &detector.find_ranges(part), *unit)
+                })
+                .collect();
+            Ok((segments, table_unit(&detectors)))
         })
-        .collect::<Vec<_>>();
+    } else {
+        run(args, &inputs, |records, session| {
+            let detectors = sport_detectors(args, limit, records, session)?;
+            let intervals = detectors
+                .iter()
+                .flat_map(|(part, detector, _)| detector.detect(&records[part.clone()]))
+                .collect();
+            Ok((intervals, table_unit(&detectors)))
+        })
+    }
+}
 
-    let mut wrtr = csv::Writer::from_writer(std::io::stdout());
-    for interval in intervals {
-        wrtr.serialize(interval).unwrap();
+/// Parses the step to resample to, which must be at least a second.
+fn parse_resample(s: &str) -> Result<NonZeroUsize, String> {
+    let step = s.parse::<usize>().map_err(|err| err.to_string())?;
+    NonZeroUsize::new(step).ok_or_else(|| "must be at least 1 second".to_owned())
+}
+
+/// Parses a stop limit, which is in the unit of `limit` when it has no unit of its own.
+fn parse_stop_limit(s: &str, limit: Limit) -> Result<Limit, String> {
+    let s = s.trim();
+    if !s
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.' || c == ':')
+    {
+        return s.parse();
+    }
+    match limit {
+        Limit::Speed(speed) => format!("{}{}", s, speed.unit()).parse(),
+        Limit::HeartRate(_) => format!("{}bpm", s).parse(),
+    }
+}
+
+/// Expands the input directories into the files they contain.
+fn find_inputs(args: &Opt) -> Result<Vec<PathBuf>, walkdir::Error> {
+    let mut files = Vec::new();
+    for input in &args.inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+
+        let max_depth = if args.recursive { usize::MAX } else { 1 };
+        for entry in WalkDir::new(input).max_depth(max_depth).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(input).unwrap_or(entry.path());
+            let included = if args.include.is_empty() {
+                Format::from_path(relative).is_some()
+            } else {
+                args.include.iter().any(|pattern| {
+                    pattern.matches_path(relative)
+                        || entry
+                            .path()
+                            .file_name()
+                            .is_some_and(|name| pattern.matches_path(Path::new(name)))
+                })
+            };
+            if included {
+                files.push(entry.into_path());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Analyzes all `inputs` and writes the results. With more than one input, or a directory, the
+/// results are combined and files that can't be analyzed are skipped, failing after writing the
+/// results of the others.
+fn run<T, F>(args: &Opt, inputs: &[PathBuf], analyze: F) -> Result<(), Error>
+where
+    T: Serialize + Summary,
+    F: Fn(&[Record], &mut SessionInfo) -> Result<(Vec<T>, SpeedUnit), Error>,
+{
+    let batch = args.inputs.len() > 1 || args.inputs.iter().any(|input| input.is_dir());
+
+    let store = args
+        .store
+        .as_ref()
+        .map(Store::open)
+        .transpose()
+        .map_err(|err| Error::Io(format!("could not open history: {}", err)))?;
+
+    let mut sessions = Vec::new();
+    let mut failed = Vec::new();
+    let mut unit = None;
+    for path in inputs {
+        let result = read_session(args, path, batch).and_then(|(mut session, records)| {
+            let (results, session_unit) = analyze(&records, &mut session)?;
+            Ok((session, results, session_unit))
+        });
+        match result {
+            Ok((session, results, session_unit)) => {
+                if let Some(store) = &store {
+                    let date = session.start_date.unwrap_or_else(|| modified(path));
+                    let entry = Entry::new(date, args.tag.clone(), session.clone(), &results);
+                    if let Err(err) = store.add(&entry) {
+                        eprintln!(
+                            "error: could not record {} in history: {}",
+                            path.display(),
+                            err
+                        );
+                    }
+                }
+                unit.get_or_insert(session_unit);
+                sessions.push((session, results));
+            }
+            Err(err) if batch => {
+                eprintln!("error: {}: {}", path.display(), err);
+                failed.push(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let unit = unit.unwrap_or(SpeedUnit::Kmph);
+    let stdout = io::stdout();
+    if batch {
+        output_result(output::write_batch(
+            stdout.lock(),
+            args.output_format,
+            &sessions,
+            unit,
+        ))?;
+        if args.output_format == OutputFormat::Csv {
+            let summary = sessions
+                .iter()
+                .map(|(session, results)| FileSummary::new(session, results))
+                .collect::<Vec<_>>();
+            output_result(output::write_summary(io::stderr(), &summary, unit))?;
+        }
+    } else if let Some((session, results)) = sessions.first() {
+        output_result(output::write(
+            stdout,
+            args.output_format,
+            session,
+            results,
+            unit,
+        ))?;
+    }
+
+    match failed.len() {
+        0 => Ok(()),
+        count => Err(Error::Batch {
+            failed: count,
+            total: inputs.len(),
+            first: Box::new(failed.swap_remove(0)),
+        }),
     }
-    wrtr.flush().unwrap();
+}
+
+/// Returns the modification time of `path` in seconds since the unix epoch, which is used as the
+/// date of the session in the history when the file has no dates.
+fn modified(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+/// Reads the records from `path` and prepares them for detection.
+fn read_session(args: &Opt, path: &Path, batch: bool) -> Result<(SessionInfo, Vec<Record>), Error> {
+    let format = args
+        .format
+        .or_else(|| Format::from_path(path))
+        .ok_or_else(|| {
+            Error::InvalidInput("unknown input format, specify one with --format".to_owned())
+        })?;
+
+    let mut records: Vec<Record> = match format {
+        Format::TomTom => tomtom::read_path(path)?,
+        Format::Gpx => gpx::read_path(path)?,
+        Format::Tcx => tcx::read_path(path)?,
+        Format::Fit => fit::read_path(path)?,
+    };
+
+    // Messages are prefixed with the file when analyzing more than one
+    let prefix = if batch {
+        format!("{}: ", path.display())
+    } else {
+        String::new()
+    };
+
+    // Outliers are rejected before resampling would spread them over several samples
+    let corrected_samples = if args.reject_outliers {
+        let corrected = reject_outliers(&mut records, args.max_speed, args.max_acceleration);
+        eprintln!("{}corrected {} outlier samples", prefix, corrected);
+        corrected
+    } else {
+        0
+    };
+
+    if let Some(step) = args.resample {
+        records = resample(&records, step, args.max_gap);
+    }
+
+    if let Some(filter) = args.smooth {
+        records = smooth(&records, filter, args.max_gap);
+    }
+
+    // Report where the recording was interrupted
+    let gaps = find_gaps(&records, args.max_gap);
+    for gap in &gaps {
+        eprintln!(
+            "{}gap in recording at {}s lasting {}s",
+            prefix, gap.start_time, gap.duration
+        );
+    }
+
+    let session = SessionInfo {
+        file: path.display().to_string(),
+        format: format.to_string(),
+        start_date: records.first().and_then(|rec| rec.timestamp),
+        records: records.len(),
+        duration: match (records.first(), records.last()) {
+            (Some(first), Some(last)) => last.time_in_seconds - first.time_in_seconds,
+            _ => 0,
+        },
+        distance: records.last().map_or(0.0, |rec| rec.distance).round() as usize,
+        limit: None,
+        gaps,
+        corrected_samples,
+    };
+    Ok((session, records))
+}
+
+/// Constructs the detector for `records`, selecting a limit from the records when none was
+/// given. Returns the detector with the unit paces are shown in.
+fn detector(
+    args: &Opt,
+    limit: Option<(Limit, Limit)>,
+    records: &[Record],
+    session: &mut SessionInfo,
+) -> Result<(IntervalDetector, SpeedUnit), Error> {
+    let (limit, stop_limit) = match limit {
+        Some(limit) => limit,
+        None => {
+            // The limit is shown in the unit of the sport, or km/h if it isn't known
+            let unit = records
+                .first()
+                .and_then(|rec| rec.sport)
+                .map_or(SpeedUnit::Kmph, Sport::unit);
+            let limit = auto_limit(records)
+                .ok_or_else(|| Error::Analysis("not enough records to select a limit".to_owned()))?
+                .to_unit(unit);
+            eprintln!("selected limit of {}", limit);
+            (Limit::Speed(limit), Limit::Speed(limit))
+        }
+    };
+    if let Limit::HeartRate(_) = limit {
+        if records.iter().all(|rec| rec.heart_rate.is_none()) {
+            return Err(Error::Analysis(
+                "the session does not contain heart rate".to_owned(),
+            ));
+        }
+    }
+
+    let detector = IntervalDetector::new(limit)
+        .try_with_stop_limit(stop_limit)
+        .map_err(Error::Usage)?
+        .with_stop_duration(args.stop_duration)
+        .with_min_duration(args.min_interval_duration)
+        .with_max_gap(args.max_gap)
+        .with_grade_adjusted(args.grade_adjusted);
+
+    session.limit = Some(limit.into());
+    Ok((detector, limit_unit(limit)))
+}
+
+/// Returns true if `records` contain more than one sport.
+fn is_multisport(records: &[Record]) -> bool {
+    records
+        .windows(2)
+        .any(|pair| pair[0].sport != pair[1].sport)
+}
+
+/// Constructs the detectors for `records`, together with the range of records they apply to and
+/// the unit paces are shown in.
+///
+/// A multisport session is detected separately for the part of every sport, with speeds in the
+/// unit of that sport. Parts in which no limit can be selected are skipped.
+fn sport_detectors(
+    args: &Opt,
+    limit: Option<(Limit, Limit)>,
+    records: &[Record],
+    session: &mut SessionInfo,
+) -> Result<Vec<(Range<usize>, IntervalDetector, SpeedUnit)>, Error> {
+    if !is_multisport(records) {
+        let sport = records.first().and_then(|rec| rec.sport);
+        let limit = sport_limit(args, sport).or(limit);
+        let (detector, unit) = detector(args, limit, records, session)?;
+        return Ok(vec![(0..records.len(), detector, unit)]);
+    }
+
+    let mut detectors = Vec::new();
+    for (sport, range) in split_by_sport(records) {
+        let part_limit = sport_limit(args, sport).or(limit);
+        let sport = sport.unwrap_or(Sport::Other);
+        match detector(args, part_limit, &records[range.clone()], session) {
+            Ok((detector, _)) => {
+                detectors.push((range, detector.with_unit(sport.unit()), sport.unit()))
+            }
+            Err(err) => eprintln!(
+                "skipping {} at {}s: {}",
+                sport, records[range.start].time_in_seconds, err
+            ),
+        }
+    }
+    session.limit = limit.map(|(limit, _)| limit.into());
+    Ok(detectors)
+}
+
+/// Returns the unit of the table, which is the unit of the detector unless the parts of a
+/// multisport session each have their own.
+fn table_unit(detectors: &[(Range<usize>, IntervalDetector, SpeedUnit)]) -> SpeedUnit {
+    match detectors {
+        [(_, _, unit)] => *unit,
+        _ => SpeedUnit::Kmph,
+    }
+}
+
+/// Returns the limit given with `--sport-limit` for `sport`, where records of an unknown sport
+/// are of the other sport.
+fn sport_limit(args: &Opt, sport: Option<Sport>) -> Option<(Limit, Limit)> {
+    let sport = sport.unwrap_or(Sport::Other);
+    args.sport_limit
+        .iter()
+        .find(|sport_limit| sport_limit.sport == sport)
+        .map(|sport_limit| (sport_limit.limit, sport_limit.limit))
+}
+
+/// Returns the unit paces are shown in, which is the unit the limit was given in.
+fn limit_unit(limit: Limit) -> SpeedUnit {
+    match limit {
+        Limit::Speed(speed) => speed.unit(),
+        Limit::HeartRate(_) => SpeedUnit::Kmph,
+    }
+}
+
+/// Returns the unit of `limit`, or else of the sport of `records` if it is known.
+fn session_unit(limit: Option<(Limit, Limit)>, records: &[Record]) -> Option<SpeedUnit> {
+    match limit {
+        Some((limit, _)) => Some(limit_unit(limit)),
+        None => records.first().and_then(|rec| rec.sport).map(Sport::unit),
+    }
+}
+
+/// Returns the laps marked on the device, or an error if there are none.
+fn device_laps(records: &[Record]) -> Result<Vec<(usize, Range<usize>)>, Error> {
+    let laps = lap_ranges(records);
+    if laps.is_empty() {
+        return Err(Error::Analysis(
+            "the session does not contain any laps".to_owned(),
+        ));
+    }
+    Ok(laps)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{find_interval, Record, Speed};
+    use super::{find_inputs, Opt};
+    use std::fs;
+    use structopt::StructOpt;
 
     #[test]
-    fn test_find_interval() {
-        let records = [
-            Record {
-                time_in_seconds: 0,
-                speed: Speed::Ms(1.0),
-                distance: 0.0,
-            },
-            Record {
-                time_in_seconds: 1,
-                speed: Speed::Ms(2.0),
-                distance: 1.0,
-            },
-            Record {
-                time_in_seconds: 2,
-                speed: Speed::Ms(1.8),
-                distance: 2.0,
-            },
-            Record {
-                time_in_seconds: 3,
-                speed: Speed::Ms(2.2),
-                distance: 2.0,
-            },
-            Record {
-                time_in_seconds: 4,
-                speed: Speed::Ms(0.0),
-                distance: 2.0,
-            },
-        ];
-
-        assert_eq!(find_interval(&records, 0, Speed::Ms(1.9)), Some(1..4));
-        assert_eq!(find_interval(&records, 0, Speed::Ms(2.1)), Some(3..4));
+    fn test_find_inputs() {
+        let dir = std::env::temp_dir().join(format!("interval_inputs_{}", std::process::id()));
+        fs::create_dir_all(dir.join("jan")).unwrap();
+        for file in [
+            "2021-02.csv",
+            "notes.txt",
+            "jan/2021-01.csv",
+            "jan/2020-12.csv",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let find = |args: &[&str]| {
+            let args = Opt::from_iter(
+                ["interval_detector"]
+                    .iter()
+                    .chain(args)
+                    .chain(&[dir.to_str().unwrap()]),
+            );
+            let mut files = find_inputs(&args)
+                .unwrap()
+                .into_iter()
+                .map(|file| {
+                    file.strip_prefix(&dir)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        let all = find(&["-r"]);
+        let included = find(&["-r", "--include", "2021-*.csv"]);
+        let nested = find(&["-r", "--include", "jan/*"]);
+        let top_level = find(&["--include", "2021-*.csv"]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(all, ["2021-02.csv", "jan/2020-12.csv", "jan/2021-01.csv"]);
+        assert_eq!(included, ["2021-02.csv", "jan/2021-01.csv"]);
+        assert_eq!(nested, ["jan/2020-12.csv", "jan/2021-01.csv"]);
+        assert_eq!(top_level, ["2021-02.csv"]);
     }
 }
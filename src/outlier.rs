@@ -0,0 +1,202 @@
+use crate::geo::haversine_distance;
+use crate::{Position, Record, Speed};
+
+/// The difference in meters up to which a distance is considered to be derived from the positions
+const DERIVED_DISTANCE_TOLERANCE: f64 = 1.0;
+
+/// Finds samples with a physically implausible speed, acceleration or position jump in `records`
+/// and replaces them by interpolating between the surrounding valid samples. Returns the number
+/// of corrected samples.
+///
+/// A sample is rejected when its speed exceeds `max_speed`, or when it jumps away from the
+/// previous valid sample and back to the next one faster than `max_speed` allows for its position
+/// or `max_acceleration` (in m/s²) for its speed. The speed and position are repaired, and the
+/// distance when it was derived from the positions, i.e. when it matches the path through them.
+pub fn reject_outliers(records: &mut [Record], max_speed: Speed, max_acceleration: f64) -> usize {
+    let max_speed = max_speed.to_ms();
+
+    let mut rejected = vec![false; records.len()];
+    let mut previous: Option<usize> = None;
+    for idx in 0..records.len() {
+        let record = &records[idx];
+        let next = records.get(idx + 1);
+        let is_outlier = record.speed.to_ms() > max_speed
+            || match (previous.map(|prev| &records[prev]), next) {
+                (Some(previous), Some(next)) => {
+                    let acceleration = |a: &Record, b: &Record| {
+                        (b.speed.to_ms() - a.speed.to_ms()).abs() / elapsed(a, b)
+                    };
+                    let jump = |a: &Record, b: &Record| match (a.position, b.position) {
+                        (Some(from), Some(to)) => haversine_distance(from, to) / elapsed(a, b),
+                        _ => 0.0,
+                    };
+                    (acceleration(previous, record) > max_acceleration
+                        && acceleration(record, next) > max_acceleration)
+                        || (jump(previous, record) > max_speed && jump(record, next) > max_speed)
+                }
+                _ => false,
+            };
+
+        if is_outlier {
+            rejected[idx] = true;
+        } else {
+            previous = Some(idx);
+        }
+    }
+
+    let mut idx = 0;
+    while idx < records.len() {
+        if !rejected[idx] {
+            idx += 1;
+            continue;
+        }
+
+        // Interpolate over the whole run of rejected samples
+        let end = (idx..records.len())
+            .find(|&end| !rejected[end])
+            .unwrap_or(records.len());
+        let before = idx.checked_sub(1).map(|before| records[before].clone());
+        let after = records.get(end).cloned();
+        let derived = before.is_some()
+            && after.is_some()
+            && path_length(&records[idx - 1..=end]).is_some_and(|path| {
+                let recorded = records[end].distance - records[idx - 1].distance;
+                (recorded - path).abs() <= DERIVED_DISTANCE_TOLERANCE
+            });
+        for record in &mut records[idx..end] {
+            repair(record, before.as_ref(), after.as_ref());
+        }
+        if derived {
+            rederive_distance(records, idx - 1, end);
+        }
+        idx = end;
+    }
+
+    rejected.iter().filter(|&&rejected| rejected).count()
+}
+
+/// Returns the length in meters of the path through the positions of `records`, or `None` if
+/// some of them lack a position.
+fn path_length(records: &[Record]) -> Option<f64> {
+    records
+        .windows(2)
+        .map(|pair| Some(haversine_distance(pair[0].position?, pair[1].position?)))
+        .sum()
+}
+
+/// Recomputes the distance of the records after `start` up to and including `end` from their
+/// positions, and shifts the distance of all later records by the same amount.
+fn rederive_distance(records: &mut [Record], start: usize, end: usize) {
+    let mut distance = records[start].distance;
+    for idx in start + 1..=end {
+        if let (Some(from), Some(to)) = (records[idx - 1].position, records[idx].position) {
+            distance += haversine_distance(from, to);
+        }
+        if idx < end {
+            records[idx].distance = distance;
+        }
+    }
+    let correction = distance - records[end].distance;
+    for record in &mut records[end..] {
+        record.distance += correction;
+    }
+}
+
+/// Time in seconds between two records, at least one second to avoid dividing by zero.
+fn elapsed(a: &Record, b: &Record) -> f64 {
+    b.time_in_seconds.saturating_sub(a.time_in_seconds).max(1) as f64
+}
+
+/// Replaces the speed and position of `record` with values interpolated between the valid
+/// records around it, or copied from the only one there is.
+fn repair(record: &mut Record, before: Option<&Record>, after: Option<&Record>) {
+    let (before, after) = match (before, after) {
+        (Some(before), Some(after)) => (before, after),
+        (Some(valid), None) | (None, Some(valid)) => (valid, valid),
+        (None, None) => return,
+    };
+
+    let factor = if after.time_in_seconds > before.time_in_seconds {
+        (record.time_in_seconds - before.time_in_seconds) as f64
+            / (after.time_in_seconds - before.time_in_seconds) as f64
+    } else {
+        0.0
+    };
+    let lerp = |a: f64, b: f64| a + (b - a) * factor;
+
+    record.speed = Speed::Ms(lerp(before.speed.to_ms(), after.speed.to_ms()));
+    if let (Some(a), Some(b)) = (before.position, after.position) {
+        record.position = Some(Position {
+            latitude: lerp(a.latitude, b.latitude),
+            longitude: lerp(a.longitude, b.longitude),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::reject_outliers;
+    use crate::geo::haversine_distance;
+    use crate::{Position, Record, Speed};
+
+    #[test]
+    fn test_reject_outliers() {
+        let mut records = [3.0, 3.0, 30.0, 3.0, 9.0, 5.0, 5.0]
+            .iter()
+            .enumerate()
+            .map(|(time, &speed)| Record::new(time, time as f64 * 3.0, Speed::Ms(speed)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(reject_outliers(&mut records, Speed::Ms(20.0), 3.0), 2);
+        let speeds = records
+            .iter()
+            .map(|rec| rec.speed.to_ms())
+            .collect::<Vec<_>>();
+        assert_eq!(speeds, vec![3.0, 3.0, 3.0, 3.0, 4.0, 5.0, 5.0]);
+    }
+
+    #[test]
+    fn test_reject_position_jump() {
+        let mut records = (0..3)
+            .map(|time| {
+                let mut record = Record::new(time, 0.0, Speed::Ms(3.0));
+                record.position = Some(Position {
+                    latitude: 52.0,
+                    longitude: 5.0,
+                });
+                record
+            })
+            .collect::<Vec<_>>();
+        records[1].position = Some(Position {
+            latitude: 52.01,
+            longitude: 5.0,
+        });
+
+        assert_eq!(reject_outliers(&mut records, Speed::Ms(20.0), 10.0), 1);
+        assert_eq!(records[1].position, records[0].position);
+    }
+
+    #[test]
+    fn test_rederive_distance() {
+        // Moving north 3 meters per second with the distance derived from the positions
+        let mut records: Vec<Record> = Vec::new();
+        for time in 0..6 {
+            let latitude = 52.0 + time as f64 * 0.000027 + if time == 2 { 0.01 } else { 0.0 };
+            let position = Position {
+                latitude,
+                longitude: 5.0,
+            };
+            let distance = records.last().map_or(0.0, |previous| {
+                previous.distance + haversine_distance(previous.position.unwrap(), position)
+            });
+            let mut record = Record::new(time, distance, Speed::Ms(3.0));
+            record.position = Some(position);
+            records.push(record);
+        }
+        assert!(records[5].distance > 2000.0);
+
+        assert_eq!(reject_outliers(&mut records, Speed::Ms(20.0), 10.0), 1);
+        assert!((records[2].distance - 6.0).abs() < 0.1);
+        assert!((records[5].distance - 15.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,819 @@
+//! Writing detection results as CSV, JSON, newline delimited JSON or a table.
+
+use crate::history::WeekTrend;
+use crate::workout::RepInfo;
+use crate::{Gap, IntervalInfo, LapOverlap, Limit, Segment, SegmentKind, Speed, SpeedUnit, Sport};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// The formats results can be written in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One row per interval, session metadata is not included
+    Csv,
+    /// A single object with the session metadata and an array of intervals
+    Json,
+    /// The session metadata followed by one interval per line, each line tagged with a `type`
+    Ndjson,
+    /// A human readable table with formatted times and paces
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+            OutputFormat::Table => write!(f, "table"),
+        }
+    }
+}
+
+/// The limit an analysis was performed with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitInfo {
+    pub value: f64,
+
+    /// One of `km/h`, `m/s`, `mph`, `kn`, `s/km`, `s/mi`, `s/500m`, `s/100m` or `bpm`
+    pub unit: String,
+}
+
+impl From<Limit> for LimitInfo {
+    fn from(limit: Limit) -> Self {
+        match limit {
+            Limit::Speed(speed) if speed.unit().is_pace() => LimitInfo {
+                value: speed.value(),
+                unit: format!("s{}", speed.unit()),
+            },
+            Limit::Speed(speed) => LimitInfo {
+                value: speed.value(),
+                unit: speed.unit().to_string(),
+            },
+            Limit::HeartRate(value) => LimitInfo {
+                value,
+                unit: "bpm".to_owned(),
+            },
+        }
+    }
+}
+
+/// Metadata of an analyzed session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// The file the session was read from
+    pub file: String,
+
+    /// The format the file was read as
+    pub format: String,
+
+    /// Seconds since the unix epoch at which the session started, if the file has dates
+    pub start_date: Option<i64>,
+
+    /// Number of records in the session
+    pub records: usize,
+
+    /// Duration in seconds
+    pub duration: usize,
+
+    /// Distance in meters
+    pub distance: usize,
+
+    /// The limit intervals were detected with, if any
+    pub limit: Option<LimitInfo>,
+
+    /// Gaps in the recording
+    pub gaps: Vec<Gap>,
+
+    /// Number of samples that were corrected as outliers
+    pub corrected_samples: usize,
+}
+
+/// The part of a result that is shown in a table.
+pub trait Summary {
+    /// Time in seconds since the start of the activity
+    fn start_time(&self) -> usize;
+
+    /// Duration in seconds
+    fn duration(&self) -> usize;
+
+    /// Distance in meters
+    fn distance(&self) -> usize;
+
+    /// Whether this is a recovery, which is not numbered or included in the totals
+    fn is_recovery(&self) -> bool {
+        false
+    }
+
+    /// The label of the row in a table, by default the rows are numbered
+    fn label(&self) -> Option<String> {
+        None
+    }
+
+    /// The sport of the row, shown in a table when there is more than one
+    fn sport(&self) -> Option<Sport> {
+        None
+    }
+
+    /// The unit the pace of the row is shown in, defaults to the unit of the table
+    fn unit(&self) -> Option<SpeedUnit> {
+        None
+    }
+}
+
+impl Summary for IntervalInfo {
+    fn start_time(&self) -> usize {
+        self.start_time
+    }
+
+    fn duration(&self) -> usize {
+        self.duration
+    }
+
+    fn distance(&self) -> usize {
+        self.distance
+    }
+
+    fn sport(&self) -> Option<Sport> {
+        self.sport
+    }
+
+    fn unit(&self) -> Option<SpeedUnit> {
+        Some(self.unit)
+    }
+}
+
+impl Summary for Segment {
+    fn start_time(&self) -> usize {
+        self.info.start_time
+    }
+
+    fn duration(&self) -> usize {
+        self.info.duration
+    }
+
+    fn distance(&self) -> usize {
+        self.info.distance
+    }
+
+    fn is_recovery(&self) -> bool {
+        self.kind == SegmentKind::Recovery
+    }
+
+    fn sport(&self) -> Option<Sport> {
+        self.info.sport
+    }
+
+    fn unit(&self) -> Option<SpeedUnit> {
+        Some(self.info.unit)
+    }
+}
+
+impl Summary for RepInfo {
+    fn start_time(&self) -> usize {
+        self.start_time
+    }
+
+    fn duration(&self) -> usize {
+        self.duration
+    }
+
+    fn distance(&self) -> usize {
+        self.distance
+    }
+
+    fn unit(&self) -> Option<SpeedUnit> {
+        Some(self.unit)
+    }
+}
+
+impl Summary for LapOverlap {
+    fn start_time(&self) -> usize {
+        self.start_time
+    }
+
+    fn duration(&self) -> usize {
+        self.duration
+    }
+
+    fn distance(&self) -> usize {
+        self.distance
+    }
+
+    fn label(&self) -> Option<String> {
+        Some(format!("{} / lap {}", self.interval, self.lap))
+    }
+}
+
+#[derive(Serialize)]
+struct Document<'a, T> {
+    session: &'a SessionInfo,
+    intervals: &'a [T],
+}
+
+#[derive(Serialize)]
+struct Line<'a, T> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    value: &'a T,
+}
+
+/// Writes `session` and its `intervals` to `writer` in `format`. Paces in a table are formatted
+/// in `unit`.
+pub fn write<W: Write, T: Serialize + Summary>(
+    mut writer: W,
+    format: OutputFormat,
+    session: &SessionInfo,
+    intervals: &[T],
+    unit: SpeedUnit,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => write_table(&mut writer, intervals, unit)?,
+        OutputFormat::Csv => {
+            let mut wrtr = csv::Writer::from_writer(&mut writer);
+            for interval in intervals {
+                wrtr.serialize(interval)?;
+            }
+            wrtr.flush()?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &Document { session, intervals })?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Ndjson => {
+            let line = Line {
+                kind: "session",
+                value: session,
+            };
+            serde_json::to_writer(&mut writer, &line)?;
+            writeln!(writer)?;
+            for interval in intervals {
+                let line = Line {
+                    kind: "interval",
+                    value: interval,
+                };
+                serde_json::to_writer(&mut writer, &line)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Summary of the intervals found in a single file of a batch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileSummary {
+    /// The file the session was read from
+    pub file: String,
+
+    /// Number of intervals, not counting recoveries
+    pub intervals: usize,
+
+    /// Total duration of the intervals in seconds
+    pub duration: usize,
+
+    /// Total distance of the intervals in meters
+    pub distance: usize,
+}
+
+impl FileSummary {
+    /// Summarizes the `intervals` found in `session`.
+    pub fn new<T: Summary>(session: &SessionInfo, intervals: &[T]) -> Self {
+        let work = intervals.iter().filter(|interval| !interval.is_recovery());
+        FileSummary {
+            file: session.file.clone(),
+            intervals: work.clone().count(),
+            duration: work.clone().map(Summary::duration).sum(),
+            distance: work.map(Summary::distance).sum(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Batch<'a, T> {
+    sessions: Vec<Document<'a, T>>,
+    summary: Vec<FileSummary>,
+}
+
+#[derive(Serialize)]
+struct FileRow<'a, T> {
+    file: &'a str,
+    #[serde(flatten)]
+    row: &'a T,
+}
+
+/// Writes the results of multiple `sessions` to `writer` in `format`.
+///
+/// Every interval is tagged with the file it was found in. JSON, NDJSON and tables also include
+/// a summary per file, for CSV it can be written separately with [`write_summary`].
+pub fn write_batch<W: Write, T: Serialize + Summary>(
+    mut writer: W,
+    format: OutputFormat,
+    sessions: &[(SessionInfo, Vec<T>)],
+    unit: SpeedUnit,
+) -> io::Result<()> {
+    let summary = sessions
+        .iter()
+        .map(|(session, intervals)| FileSummary::new(session, intervals))
+        .collect::<Vec<_>>();
+
+    match format {
+        OutputFormat::Table => {
+            for (session, intervals) in sessions {
+                writeln!(writer, "{}", session.file)?;
+                write_table(&mut writer, intervals, unit)?;
+                writeln!(writer)?;
+            }
+            write_summary(&mut writer, &summary, unit)?;
+        }
+        OutputFormat::Csv => {
+            // The csv crate can't add a column to a serialized struct, so the rows are written
+            // to a buffer first and copied with the file in front.
+            let mut buffer = csv::Writer::from_writer(Vec::new());
+            for (_, intervals) in sessions {
+                for interval in intervals {
+                    buffer.serialize(interval)?;
+                }
+            }
+            let buffer = buffer
+                .into_inner()
+                .map_err(|err| io::Error::other(err.to_string()))?;
+
+            let files = sessions.iter().flat_map(|(session, intervals)| {
+                std::iter::repeat_n(session.file.as_str(), intervals.len())
+            });
+            let mut rows = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(buffer.as_slice())
+                .into_records();
+            let mut wrtr = csv::Writer::from_writer(&mut writer);
+            if let Some(header) = rows.next() {
+                wrtr.write_record(std::iter::once("file").chain(&header?))?;
+            }
+            for (file, row) in files.zip(rows) {
+                wrtr.write_record(std::iter::once(file).chain(&row?))?;
+            }
+            wrtr.flush()?;
+        }
+        OutputFormat::Json => {
+            let batch = Batch {
+                sessions: sessions
+                    .iter()
+                    .map(|(session, intervals)| Document { session, intervals })
+                    .collect(),
+                summary,
+            };
+            serde_json::to_writer_pretty(&mut writer, &batch)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Ndjson => {
+            for (session, intervals) in sessions {
+                let line = Line {
+                    kind: "session",
+                    value: session,
+                };
+                serde_json::to_writer(&mut writer, &line)?;
+                writeln!(writer)?;
+                for interval in intervals {
+                    let line = Line {
+                        kind: "interval",
+                        value: &FileRow {
+                            file: &session.file,
+                            row: interval,
+                        },
+                    };
+                    serde_json::to_writer(&mut writer, &line)?;
+                    writeln!(writer)?;
+                }
+            }
+            for file in &summary {
+                let line = Line {
+                    kind: "summary",
+                    value: file,
+                };
+                serde_json::to_writer(&mut writer, &line)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Writes the per file `summary` of a batch as an aligned table.
+pub fn write_summary<W: Write>(
+    mut writer: W,
+    summary: &[FileSummary],
+    unit: SpeedUnit,
+) -> io::Result<()> {
+    let mut rows = vec![[
+        "File".to_owned(),
+        "Intervals".to_owned(),
+        "Duration".to_owned(),
+        "Distance".to_owned(),
+        "Pace".to_owned(),
+    ]];
+    for file in summary {
+        rows.push([
+            file.file.clone(),
+            file.intervals.to_string(),
+            format_duration(file.duration, false),
+            format!("{} m", file.distance),
+            format_pace(file.duration, file.distance, unit),
+        ]);
+    }
+
+    let widths = column_widths(&rows);
+    for row in &rows {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(idx, (cell, width))| {
+                if idx == 0 {
+                    format!("{:<width$}", cell, width = width)
+                } else {
+                    format!("{:>width$}", cell, width = width)
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", cells.join("  "))?;
+    }
+    Ok(())
+}
+
+/// Writes the weekly `trends` of the history to `writer` in `format`. Paces in a table are
+/// formatted in `unit`.
+pub fn write_trends<W: Write>(
+    mut writer: W,
+    format: OutputFormat,
+    trends: &[WeekTrend],
+    unit: SpeedUnit,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => {
+            let mut rows = vec![[
+                "Week".to_owned(),
+                "Sessions".to_owned(),
+                "Intervals".to_owned(),
+                "Duration".to_owned(),
+                "Distance".to_owned(),
+                "Pace".to_owned(),
+            ]];
+            for trend in trends {
+                rows.push([
+                    trend.week.clone(),
+                    trend.sessions.to_string(),
+                    trend.intervals.to_string(),
+                    format_duration(trend.duration, false),
+                    format!("{} m", trend.distance),
+                    format_pace(trend.duration, trend.distance, unit),
+                ]);
+            }
+            let widths = column_widths(&rows);
+            for row in &rows {
+                let cells = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+                    .collect::<Vec<_>>();
+                writeln!(writer, "{}", cells.join("  "))?;
+            }
+        }
+        OutputFormat::Csv => {
+            let mut wrtr = csv::Writer::from_writer(&mut writer);
+            for trend in trends {
+                wrtr.serialize(trend)?;
+            }
+            wrtr.flush()?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, trends)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Ndjson => {
+            for trend in trends {
+                serde_json::to_writer(&mut writer, trend)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Writes `intervals` as an aligned table followed by a row with the totals of all intervals
+/// that are not a recovery.
+fn write_table<W: Write, T: Summary>(
+    mut writer: W,
+    intervals: &[T],
+    unit: SpeedUnit,
+) -> io::Result<()> {
+    let row = |number: String,
+               sport: Option<Sport>,
+               start: String,
+               duration: usize,
+               distance: usize,
+               unit: SpeedUnit| {
+        [
+            number,
+            sport.map_or_else(String::new, |sport| sport.to_string()),
+            start,
+            format_duration(duration, false),
+            format!("{} m", distance),
+            format_pace(duration, distance, unit),
+        ]
+    };
+
+    let mut rows = vec![[
+        "#".to_owned(),
+        "Sport".to_owned(),
+        "Start".to_owned(),
+        "Duration".to_owned(),
+        "Distance".to_owned(),
+        "Pace".to_owned(),
+    ]];
+    let mut number = 0;
+    for interval in intervals {
+        let label = if interval.is_recovery() {
+            "rest".to_owned()
+        } else {
+            number += 1;
+            interval.label().unwrap_or_else(|| number.to_string())
+        };
+        rows.push(row(
+            label,
+            interval.sport(),
+            format_duration(interval.start_time(), true),
+            interval.duration(),
+            interval.distance(),
+            interval.unit().unwrap_or(unit),
+        ));
+    }
+    let work = intervals.iter().filter(|interval| !interval.is_recovery());
+    rows.push(row(
+        "Total".to_owned(),
+        None,
+        String::new(),
+        work.clone().map(Summary::duration).sum(),
+        work.map(Summary::distance).sum(),
+        unit,
+    ));
+
+    // The sport is only shown when the session contains more than one
+    let mut sports = intervals.iter().filter_map(Summary::sport);
+    let show_sport = match sports.next() {
+        Some(first) => sports.any(|sport| sport != first),
+        None => false,
+    };
+    let columns = (0..6)
+        .filter(|&column| show_sport || column != 1)
+        .collect::<Vec<_>>();
+
+    let widths = column_widths(&rows);
+    for (idx, row) in rows.iter().enumerate() {
+        if idx == rows.len() - 1 {
+            let total_width = columns.iter().map(|&column| widths[column]).sum::<usize>()
+                + 2 * (columns.len() - 1);
+            writeln!(writer, "{}", "-".repeat(total_width))?;
+        }
+        let cells = columns
+            .iter()
+            .map(|&column| format!("{:>width$}", row[column], width = widths[column]))
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", cells.join("  "))?;
+    }
+    Ok(())
+}
+
+/// Returns the width of the widest cell in every column of `rows`.
+fn column_widths<const N: usize>(rows: &[[String; N]]) -> [usize; N] {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    widths
+}
+
+/// Formats the average speed over `distance` meters in `duration` seconds in `unit`, or `-` when
+/// there is no speed.
+fn format_pace(duration: usize, distance: usize, unit: SpeedUnit) -> String {
+    if duration > 0 && distance > 0 {
+        Speed::Ms(distance as f64 / duration as f64)
+            .to_unit(unit)
+            .to_string()
+    } else {
+        "-".to_owned()
+    }
+}
+
+/// Formats a number of seconds as `m:ss`, or `mm:ss` when `pad` is set.
+fn format_duration(seconds: usize, pad: bool) -> String {
+    if pad {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        write, write_batch, write_summary, FileSummary, OutputFormat, SessionInfo, Summary,
+    };
+    use crate::{Gap, IntervalInfo, Limit, Record, Speed, SpeedUnit, Sport};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Interval {
+        start_time: usize,
+    }
+
+    impl Summary for Interval {
+        fn start_time(&self) -> usize {
+            self.start_time
+        }
+
+        fn duration(&self) -> usize {
+            100
+        }
+
+        fn distance(&self) -> usize {
+            400
+        }
+    }
+
+    fn session() -> SessionInfo {
+        SessionInfo {
+            file: "session.csv".to_owned(),
+            format: "csv".to_owned(),
+            start_date: None,
+            records: 100,
+            duration: 99,
+            distance: 300,
+            limit: Some(Limit::Speed(Speed::Kmph(12.0)).into()),
+            gaps: vec![Gap {
+                start_time: 10,
+                duration: 20,
+            }],
+            corrected_samples: 2,
+        }
+    }
+
+    #[test]
+    fn test_write_ndjson() {
+        let mut output = Vec::new();
+        let intervals = [Interval { start_time: 5 }, Interval { start_time: 50 }];
+        write(
+            &mut output,
+            OutputFormat::Ndjson,
+            &session(),
+            &intervals,
+            SpeedUnit::Kmph,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"type":"session","file":"session.csv","format":"csv","start_date":null,"records":100,"duration":99,"distance":300,"limit":{"value":12.0,"unit":"km/h"},"gaps":[{"start_time":10,"duration":20}],"corrected_samples":2}"#,
+                r#"{"type":"interval","start_time":5}"#,
+                r#"{"type":"interval","start_time":50}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut output = Vec::new();
+        write(
+            &mut output,
+            OutputFormat::Json,
+            &session(),
+            &[Interval { start_time: 5 }],
+            SpeedUnit::Kmph,
+        )
+        .unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(value["session"]["limit"]["unit"], "km/h");
+        assert_eq!(value["intervals"][0]["start_time"], 5);
+    }
+
+    #[test]
+    fn test_write_table() {
+        let mut output = Vec::new();
+        let intervals = [Interval { start_time: 65 }, Interval { start_time: 3725 }];
+        write(
+            &mut output,
+            OutputFormat::Table,
+            &session(),
+            &intervals,
+            SpeedUnit::SecPer500m,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "    #  Start  Duration  Distance         Pace\n",
+                "    1  01:05      1:40     400 m  2:05.0/500m\n",
+                "    2  62:05      1:40     400 m  2:05.0/500m\n",
+                "---------------------------------------------\n",
+                "Total             3:20     800 m  2:05.0/500m\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_batch_csv() {
+        let mut other = session();
+        other.file = "other.csv".to_owned();
+        let sessions = vec![
+            (session(), vec![Interval { start_time: 65 }]),
+            (
+                other,
+                vec![Interval { start_time: 10 }, Interval { start_time: 300 }],
+            ),
+        ];
+
+        let mut output = Vec::new();
+        write_batch(&mut output, OutputFormat::Csv, &sessions, SpeedUnit::Kmph).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "file,start_time\nsession.csv,65\nother.csv,10\nother.csv,300\n"
+        );
+
+        let mut output = Vec::new();
+        let summary = sessions
+            .iter()
+            .map(|(session, intervals)| FileSummary::new(session, intervals))
+            .collect::<Vec<_>>();
+        write_summary(&mut output, &summary, SpeedUnit::Kmph).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "File         Intervals  Duration  Distance       Pace\n",
+                "session.csv          1      1:40     400 m  14.4 km/h\n",
+                "other.csv            2      3:20     800 m  14.4 km/h\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_table_sports() {
+        let records = |sport| {
+            (0..=100)
+                .map(|time| {
+                    let mut record = Record::new(time, time as f64 * 4.0, Speed::Ms(4.0));
+                    record.sport = Some(sport);
+                    record
+                })
+                .collect::<Vec<_>>()
+        };
+        let intervals = [
+            IntervalInfo::from_range(&records(Sport::Running), 0..101, SpeedUnit::SecPerKm),
+            IntervalInfo::from_range(&records(Sport::Cycling), 0..101, SpeedUnit::Kmph),
+        ];
+
+        let mut output = Vec::new();
+        write(
+            &mut output,
+            OutputFormat::Table,
+            &session(),
+            &intervals,
+            SpeedUnit::Kmph,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "    #    Sport  Start  Duration  Distance       Pace\n",
+                "    1  running  00:00      1:40     400 m    4:10/km\n",
+                "    2  cycling  00:00      1:40     400 m  14.4 km/h\n",
+                "----------------------------------------------------\n",
+                "Total                      3:20     800 m  14.4 km/h\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{Speed, Sport};
+
+/// A position on the earth in degrees.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// A single sample of an activity, as used by the detector.
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// Time since the start of the activity
+    pub time_in_seconds: usize,
+
+    /// Seconds since the unix epoch at which this sample was recorded, if the file has dates
+    pub timestamp: Option<i64>,
+
+    /// Cumulative distance in meters since the start of the activity
+    pub distance: f64,
+
+    /// Instantaneous speed at this sample
+    pub speed: Speed,
+
+    /// GPS position, if recorded
+    pub position: Option<Position>,
+
+    /// Elevation in meters, if recorded
+    pub elevation: Option<f64>,
+
+    /// Heart rate in beats per minute, if recorded
+    pub heart_rate: Option<f64>,
+
+    /// Cadence in steps or strokes per minute, if recorded
+    pub cadence: Option<f64>,
+
+    /// Cumulative calories burned in kcal since the start of the activity, if recorded
+    pub calories: Option<f64>,
+
+    /// The lap recorded by the device this sample belongs to, starting at 1
+    pub lap_number: Option<usize>,
+
+    /// The sport this sample was recorded as, if known
+    pub sport: Option<Sport>,
+}
+
+impl Record {
+    /// Constructs a record without any of the optional measurements.
+    pub fn new(time_in_seconds: usize, distance: f64, speed: Speed) -> Self {
+        Record {
+            time_in_seconds,
+            timestamp: None,
+            distance,
+            speed,
+            position: None,
+            elevation: None,
+            heart_rate: None,
+            cadence: None,
+            calories: None,
+            lap_number: None,
+            sport: None,
+        }
+    }
+}
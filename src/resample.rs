@@ -0,0 +1,103 @@
+use crate::{Position, Record, Speed};
+use std::num::NonZeroUsize;
+
+/// Resamples `records` onto a uniform time grid with a spacing of `step` seconds, starting at the
+/// first record.
+///
+/// Values between records are linearly interpolated. Grid points that fall inside a gap of more
+/// than `max_gap` seconds are skipped so gaps in the recording are preserved.
+pub fn resample(records: &[Record], step: NonZeroUsize, max_gap: usize) -> Vec<Record> {
+    let step = step.get();
+    let (first, last) = match (records.first(), records.last()) {
+        (Some(first), Some(last)) => (first.time_in_seconds, last.time_in_seconds),
+        _ => return Vec::new(),
+    };
+
+    let mut result = Vec::with_capacity((last - first) / step + 1);
+    let mut idx = 0;
+    for time in (first..=last).step_by(step) {
+        while idx + 1 < records.len() && records[idx + 1].time_in_seconds <= time {
+            idx += 1;
+        }
+
+        let before = &records[idx];
+        if before.time_in_seconds == time {
+            result.push(before.clone());
+            continue;
+        }
+
+        let after = &records[idx + 1];
+        if after.time_in_seconds - before.time_in_seconds <= max_gap {
+            result.push(interpolate(before, after, time));
+        }
+    }
+
+    result
+}
+
+/// Linearly interpolates between two records at `time`.
+fn interpolate(before: &Record, after: &Record, time: usize) -> Record {
+    let factor = (time - before.time_in_seconds) as f64
+        / (after.time_in_seconds - before.time_in_seconds) as f64;
+    let lerp = |a: f64, b: f64| a + (b - a) * factor;
+    let lerp_option = |a: Option<f64>, b: Option<f64>| Some(lerp(a?, b?));
+
+    Record {
+        time_in_seconds: time,
+        timestamp: before
+            .timestamp
+            .map(|timestamp| timestamp + (time - before.time_in_seconds) as i64),
+        distance: lerp(before.distance, after.distance),
+        speed: Speed::Ms(lerp(before.speed.to_ms(), after.speed.to_ms())),
+        position: match (before.position, after.position) {
+            (Some(a), Some(b)) => Some(Position {
+                latitude: lerp(a.latitude, b.latitude),
+                longitude: lerp(a.longitude, b.longitude),
+            }),
+            _ => None,
+        },
+        elevation: lerp_option(before.elevation, after.elevation),
+        heart_rate: lerp_option(before.heart_rate, after.heart_rate),
+        cadence: lerp_option(before.cadence, after.cadence),
+        calories: lerp_option(before.calories, after.calories),
+        lap_number: before.lap_number,
+        sport: before.sport,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::resample;
+    use crate::{Record, Speed};
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn test_resample() {
+        let mut records = vec![
+            Record::new(0, 0.0, Speed::Ms(2.0)),
+            Record::new(2, 4.0, Speed::Ms(2.0)),
+            Record::new(6, 16.0, Speed::Ms(4.0)),
+            Record::new(30, 16.0, Speed::Ms(0.0)),
+        ];
+        records[0].heart_rate = Some(100.0);
+        records[1].heart_rate = Some(110.0);
+
+        let resampled = resample(&records, NonZeroUsize::new(1).unwrap(), 10);
+        let times = resampled
+            .iter()
+            .map(|rec| rec.time_in_seconds)
+            .collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 1, 2, 3, 4, 5, 6, 30]);
+        assert_eq!(resampled[1].distance, 2.0);
+        assert_eq!(resampled[1].heart_rate, Some(105.0));
+        assert_eq!(resampled[4].distance, 10.0);
+        assert_eq!(resampled[4].speed.to_ms(), 3.0);
+        assert_eq!(resampled[4].heart_rate, None);
+
+        let times = resample(&records, NonZeroUsize::new(4).unwrap(), 30)
+            .iter()
+            .map(|rec| rec.time_in_seconds)
+            .collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 4, 8, 12, 16, 20, 24, 28]);
+    }
+}
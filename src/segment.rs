@@ -0,0 +1,127 @@
+use crate::{IntervalInfo, Record, SpeedUnit};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::Serialize;
+use std::ops::Range;
+
+/// Whether a segment is an interval or the recovery between two intervals.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentKind {
+    Interval,
+    Recovery,
+}
+
+/// Summary of a part of the session, used to report its work:rest structure.
+///
+/// Has the statistics of [`IntervalInfo`], together with the heart rate at the start and end of
+/// the segment to show how far it dropped during a recovery. It is serialized as a single row with
+/// the kind followed by the fields of the statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+
+    /// The statistics of the records in the segment
+    pub info: IntervalInfo,
+
+    /// Heart rate in beats per minute at the start of the segment
+    pub heart_rate_start: Option<f64>,
+
+    /// Heart rate in beats per minute at the end of the segment
+    pub heart_rate_end: Option<f64>,
+}
+
+impl Segment {
+    /// Summarizes the records in `range`, with speeds expressed in `unit`.
+    pub fn from_range(
+        kind: SegmentKind,
+        records: &[Record],
+        range: Range<usize>,
+        unit: SpeedUnit,
+    ) -> Self {
+        Segment {
+            kind,
+            heart_rate_start: records[range.start].heart_rate,
+            heart_rate_end: records[range.end - 1].heart_rate,
+            info: IntervalInfo::from_range(records, range, unit),
+        }
+    }
+}
+
+impl Serialize for Segment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Segment", IntervalInfo::FIELDS + 3)?;
+        state.serialize_field("kind", &self.kind)?;
+        self.info.serialize_fields(&mut state)?;
+        state.serialize_field("heart_rate_start", &self.heart_rate_start)?;
+        state.serialize_field("heart_rate_end", &self.heart_rate_end)?;
+        state.end()
+    }
+}
+
+/// Returns the intervals in `records` interleaved with the recoveries between them.
+///
+/// A recovery runs from the last record of an interval to the first record of the next one.
+/// Speeds are expressed in `unit`.
+pub fn segments(records: &[Record], intervals: &[Range<usize>], unit: SpeedUnit) -> Vec<Segment> {
+    let mut result = Vec::with_capacity(intervals.len() * 2);
+    for (idx, interval) in intervals.iter().enumerate() {
+        if idx > 0 {
+            let recovery = intervals[idx - 1].end - 1..interval.start + 1;
+            result.push(Segment::from_range(
+                SegmentKind::Recovery,
+                records,
+                recovery,
+                unit,
+            ));
+        }
+        result.push(Segment::from_range(
+            SegmentKind::Interval,
+            records,
+            interval.clone(),
+            unit,
+        ));
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{segments, SegmentKind};
+    use crate::{Record, Speed, SpeedUnit};
+
+    #[test]
+    fn test_segments() {
+        let mut records = (0..10)
+            .map(|time| Record::new(time, time as f64 * 2.0, Speed::Ms(2.0)))
+            .collect::<Vec<_>>();
+        records[3].heart_rate = Some(170.0);
+        records[6].heart_rate = Some(130.0);
+
+        let segments = segments(&records, &[0..4, 6..9], SpeedUnit::Kmph);
+        let kinds = segments.iter().map(|seg| seg.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                SegmentKind::Interval,
+                SegmentKind::Recovery,
+                SegmentKind::Interval
+            ]
+        );
+
+        let recovery = &segments[1];
+        assert_eq!(recovery.info.start_time, 3);
+        assert_eq!(recovery.info.duration, 3);
+        assert_eq!(recovery.info.distance, 6);
+        assert_eq!(recovery.info.average_speed, 7.2);
+        assert_eq!(recovery.info.unit, SpeedUnit::Kmph);
+        assert_eq!(recovery.heart_rate_start, Some(170.0));
+        assert_eq!(recovery.heart_rate_end, Some(130.0));
+
+        // Segments are written as a flat row with the statistics of an interval
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(recovery).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(csv.starts_with("kind,start_time,duration,distance,"));
+        assert!(csv.contains("\nrecovery,3,3,6,"));
+    }
+}
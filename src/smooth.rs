@@ -0,0 +1,300 @@
+use crate::{split_at_gaps, Record, Speed};
+use std::fmt;
+use std::str::FromStr;
+
+/// Filters to smooth the speed of the records before detecting intervals.
+///
+/// Filters can be parsed from strings like `median:5` or `kalman`, where the number after the
+/// colon is optional and sets the window or strength of the filter.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    /// Centered moving average over a window of seconds
+    MovingAverage(usize),
+    /// Exponential smoothing with a time constant in seconds
+    Exponential(f64),
+    /// Centered median over a window of seconds
+    Median(usize),
+    /// Kalman filter on distance and speed with the given acceleration noise in m/s²
+    Kalman(f64),
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = match s.split_once(':') {
+            Some((name, parameter)) => (name.trim(), Some(parameter.trim())),
+            None => (s.trim(), None),
+        };
+        let invalid = || format!("invalid parameter for filter '{}'", name);
+        let window = |default| match parameter {
+            Some(parameter) => parameter
+                .parse::<usize>()
+                .ok()
+                .filter(|&window| window > 0)
+                .ok_or_else(invalid),
+            None => Ok(default),
+        };
+        let strength = |default| match parameter {
+            Some(parameter) => parameter
+                .parse::<f64>()
+                .ok()
+                .filter(|&value| value > 0.0)
+                .ok_or_else(invalid),
+            None => Ok(default),
+        };
+
+        match name.to_ascii_lowercase().as_str() {
+            "moving-average" | "average" | "sma" => Ok(Filter::MovingAverage(window(5)?)),
+            "exponential" | "ema" => Ok(Filter::Exponential(strength(3.0)?)),
+            "median" => Ok(Filter::Median(window(5)?)),
+            "kalman" => Ok(Filter::Kalman(strength(0.5)?)),
+            _ => Err(format!("unknown filter '{}'", name)),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::MovingAverage(window) => write!(f, "moving-average:{}", window),
+            Filter::Exponential(time_constant) => write!(f, "exponential:{}", time_constant),
+            Filter::Median(window) => write!(f, "median:{}", window),
+            Filter::Kalman(noise) => write!(f, "kalman:{}", noise),
+        }
+    }
+}
+
+/// Smooths the speed of `records` with `filter`.
+///
+/// The records are filtered separately between gaps of more than `max_gap` seconds, so samples
+/// from before a gap don't affect the speed after it. All other values are left unchanged.
+pub fn smooth(records: &[Record], filter: Filter, max_gap: usize) -> Vec<Record> {
+    let mut result = records.to_vec();
+    for range in split_at_gaps(records, max_gap) {
+        let segment = &mut result[range];
+        let speeds = match filter {
+            Filter::MovingAverage(window) => moving_average(segment, window),
+            Filter::Exponential(time_constant) => exponential(segment, time_constant),
+            Filter::Median(window) => median(segment, window),
+            Filter::Kalman(noise) => kalman(segment, noise),
+        };
+        for (record, speed) in segment.iter_mut().zip(speeds) {
+            record.speed = Speed::Ms(speed);
+        }
+    }
+    result
+}
+
+/// Returns the range of records within `window / 2` seconds of every record.
+fn windows(records: &[Record], window: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let half = window / 2;
+    let mut start = 0;
+    let mut end = 0;
+    records.iter().map(move |record| {
+        let time = record.time_in_seconds;
+        while records[start].time_in_seconds + half < time {
+            start += 1;
+        }
+        while end < records.len() && records[end].time_in_seconds <= time + half {
+            end += 1;
+        }
+        (start, end)
+    })
+}
+
+fn moving_average(records: &[Record], window: usize) -> Vec<f64> {
+    windows(records, window)
+        .map(|(start, end)| {
+            let sum: f64 = records[start..end]
+                .iter()
+                .map(|rec| rec.speed.to_ms())
+                .sum();
+            sum / (end - start) as f64
+        })
+        .collect()
+}
+
+fn median(records: &[Record], window: usize) -> Vec<f64> {
+    windows(records, window)
+        .map(|(start, end)| {
+            let mut speeds = records[start..end]
+                .iter()
+                .map(|rec| rec.speed.to_ms())
+                .collect::<Vec<_>>();
+            speeds.sort_by(|a, b| a.total_cmp(b));
+            let middle = speeds.len() / 2;
+            if speeds.len() % 2 == 0 {
+                (speeds[middle - 1] + speeds[middle]) / 2.0
+            } else {
+                speeds[middle]
+            }
+        })
+        .collect()
+}
+
+/// Exponential smoothing where the weight of a sample depends on the time since the previous one,
+/// so irregular sampling doesn't change the strength of the filter.
+fn exponential(records: &[Record], time_constant: f64) -> Vec<f64> {
+    let mut smoothed: Option<(usize, f64)> = None;
+    records
+        .iter()
+        .map(|record| {
+            let speed = record.speed.to_ms();
+            let value = match smoothed {
+                Some((time, value)) => {
+                    let dt = (record.time_in_seconds - time) as f64;
+                    let alpha = 1.0 - (-dt / time_constant).exp();
+                    value + alpha * (speed - value)
+                }
+                None => speed,
+            };
+            smoothed = Some((record.time_in_seconds, value));
+            value
+        })
+        .collect()
+}
+
+/// Variance of the measured distance in m²
+const DISTANCE_VARIANCE: f64 = 25.0;
+
+/// Variance of the measured speed in (m/s)²
+const SPEED_VARIANCE: f64 = 1.0;
+
+/// Kalman filter with a constant velocity model, measuring both the distance and the speed of
+/// every record. `noise` is the standard deviation of the acceleration in m/s².
+fn kalman(records: &[Record], noise: f64) -> Vec<f64> {
+    let first = match records.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+
+    // State of distance and speed with its covariance
+    let mut state = [first.distance, first.speed.to_ms()];
+    let mut cov = [[DISTANCE_VARIANCE, 0.0], [0.0, SPEED_VARIANCE]];
+    let mut time = first.time_in_seconds;
+    let q = noise * noise;
+
+    let mut speeds = Vec::with_capacity(records.len());
+    speeds.push(state[1]);
+    for record in &records[1..] {
+        // Predict
+        let dt = (record.time_in_seconds - time) as f64;
+        time = record.time_in_seconds;
+        state[0] += state[1] * dt;
+        cov = [
+            [
+                cov[0][0]
+                    + dt * (cov[0][1] + cov[1][0])
+                    + dt * dt * cov[1][1]
+                    + q * dt.powi(4) / 4.0,
+                cov[0][1] + dt * cov[1][1] + q * dt.powi(3) / 2.0,
+            ],
+            [
+                cov[1][0] + dt * cov[1][1] + q * dt.powi(3) / 2.0,
+                cov[1][1] + q * dt * dt,
+            ],
+        ];
+
+        // Update with the measured distance and speed
+        let s = [
+            [cov[0][0] + DISTANCE_VARIANCE, cov[0][1]],
+            [cov[1][0], cov[1][1] + SPEED_VARIANCE],
+        ];
+        let det = s[0][0] * s[1][1] - s[0][1] * s[1][0];
+        let inverse = [
+            [s[1][1] / det, -s[0][1] / det],
+            [-s[1][0] / det, s[0][0] / det],
+        ];
+        let gain = [
+            [
+                cov[0][0] * inverse[0][0] + cov[0][1] * inverse[1][0],
+                cov[0][0] * inverse[0][1] + cov[0][1] * inverse[1][1],
+            ],
+            [
+                cov[1][0] * inverse[0][0] + cov[1][1] * inverse[1][0],
+                cov[1][0] * inverse[0][1] + cov[1][1] * inverse[1][1],
+            ],
+        ];
+        let residual = [record.distance - state[0], record.speed.to_ms() - state[1]];
+        state[0] += gain[0][0] * residual[0] + gain[0][1] * residual[1];
+        state[1] += gain[1][0] * residual[0] + gain[1][1] * residual[1];
+        cov = [
+            [
+                (1.0 - gain[0][0]) * cov[0][0] - gain[0][1] * cov[1][0],
+                (1.0 - gain[0][0]) * cov[0][1] - gain[0][1] * cov[1][1],
+            ],
+            [
+                -gain[1][0] * cov[0][0] + (1.0 - gain[1][1]) * cov[1][0],
+                -gain[1][0] * cov[0][1] + (1.0 - gain[1][1]) * cov[1][1],
+            ],
+        ];
+
+        speeds.push(state[1].max(0.0));
+    }
+    speeds
+}
+
+#[cfg(test)]
+mod test {
+    use super::{smooth, Filter};
+    use crate::{Record, Speed};
+
+    fn records(speeds: &[f64]) -> Vec<Record> {
+        let mut distance = 0.0;
+        speeds
+            .iter()
+            .enumerate()
+            .map(|(time, &speed)| {
+                distance += speed;
+                Record::new(time, distance, Speed::Ms(speed))
+            })
+            .collect()
+    }
+
+    fn speeds(records: &[Record]) -> Vec<f64> {
+        records.iter().map(|rec| rec.speed.to_ms()).collect()
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!("median".parse(), Ok(Filter::Median(5)));
+        assert_eq!("moving-average:7".parse(), Ok(Filter::MovingAverage(7)));
+        assert_eq!("ema:2.5".parse(), Ok(Filter::Exponential(2.5)));
+        assert_eq!("Kalman:1".parse(), Ok(Filter::Kalman(1.0)));
+        assert!("median:0".parse::<Filter>().is_err());
+        assert!("gaussian".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn test_smooth() {
+        let records = records(&[3.0, 3.0, 9.0, 3.0, 3.0]);
+        assert_eq!(
+            speeds(&smooth(&records, Filter::Median(3), 10)),
+            vec![3.0, 3.0, 3.0, 3.0, 3.0]
+        );
+        assert_eq!(
+            speeds(&smooth(&records, Filter::MovingAverage(3), 10)),
+            vec![3.0, 5.0, 5.0, 5.0, 3.0]
+        );
+
+        let smoothed = speeds(&smooth(&records, Filter::Exponential(2.0), 10));
+        assert_eq!(smoothed[1], 3.0);
+        assert!(smoothed[2] > 3.0 && smoothed[2] < 9.0);
+        assert!(smoothed[3] < smoothed[2]);
+
+        let smoothed = speeds(&smooth(&records, Filter::Kalman(0.5), 10));
+        assert!(smoothed[2] > 3.0 && smoothed[2] < 9.0);
+    }
+
+    #[test]
+    fn test_smooth_does_not_cross_gaps() {
+        let mut records = records(&[3.0, 3.0, 9.0, 9.0]);
+        records[2].time_in_seconds = 60;
+        records[3].time_in_seconds = 61;
+        assert_eq!(
+            speeds(&smooth(&records, Filter::MovingAverage(3), 10)),
+            vec![3.0, 3.0, 9.0, 9.0]
+        );
+    }
+}
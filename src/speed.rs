@@ -0,0 +1,308 @@
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Meters in a statute mile
+const METERS_PER_MILE: f64 = 1609.344;
+
+/// Meters in a nautical mile
+const METERS_PER_NAUTICAL_MILE: f64 = 1852.0;
+
+/// A speed expressed in one of the units commonly used by athletes.
+///
+/// Speeds can be parsed from and formatted as strings like `15.2 km/h`, `4:30/km` or
+/// `1:52.3/500m`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Speed {
+    Kmph(f64),
+    Ms(f64),
+    Mph(f64),
+    Knots(f64),
+    SecPerKm(f64),
+    SecPerMile(f64),
+    SecPer500m(f64),
+    SecPer100m(f64),
+}
+
+impl PartialOrd for Speed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.to_ms().partial_cmp(&other.to_ms())
+    }
+}
+
+impl Speed {
+    /// Convert the speed to a meters per second
+    pub fn to_ms(self) -> f64 {
+        match self {
+            Speed::Kmph(kmph) => kmph / 3.6,
+            Speed::Ms(ms) => ms,
+            Speed::Mph(mph) => mph * METERS_PER_MILE / 3600.0,
+            Speed::Knots(knots) => knots * METERS_PER_NAUTICAL_MILE / 3600.0,
+            Speed::SecPerKm(pace) => 1000.0 / pace,
+            Speed::SecPerMile(pace) => METERS_PER_MILE / pace,
+            Speed::SecPer500m(pace) => 500.0 / pace,
+            Speed::SecPer100m(pace) => 100.0 / pace,
+        }
+    }
+
+    /// Convert the speed to kilometers per hour
+    pub fn to_kmph(self) -> f64 {
+        self.to_ms() * 3.6
+    }
+
+    /// Convert the speed to pace
+    pub fn to_pace(self) -> f64 {
+        500.0 / self.to_ms()
+    }
+
+    /// Returns the unit the speed is expressed in
+    pub fn unit(self) -> SpeedUnit {
+        match self {
+            Speed::Kmph(_) => SpeedUnit::Kmph,
+            Speed::Ms(_) => SpeedUnit::Ms,
+            Speed::Mph(_) => SpeedUnit::Mph,
+            Speed::Knots(_) => SpeedUnit::Knots,
+            Speed::SecPerKm(_) => SpeedUnit::SecPerKm,
+            Speed::SecPerMile(_) => SpeedUnit::SecPerMile,
+            Speed::SecPer500m(_) => SpeedUnit::SecPer500m,
+            Speed::SecPer100m(_) => SpeedUnit::SecPer100m,
+        }
+    }
+
+    /// Returns the numeric value of the speed in its unit
+    pub fn value(self) -> f64 {
+        match self {
+            Speed::Kmph(value)
+            | Speed::Ms(value)
+            | Speed::Mph(value)
+            | Speed::Knots(value)
+            | Speed::SecPerKm(value)
+            | Speed::SecPerMile(value)
+            | Speed::SecPer500m(value)
+            | Speed::SecPer100m(value) => value,
+        }
+    }
+
+    /// Convert the speed to `unit`
+    pub fn to_unit(self, unit: SpeedUnit) -> Speed {
+        let ms = self.to_ms();
+        let value = match unit {
+            SpeedUnit::Kmph => ms * 3.6,
+            SpeedUnit::Ms => ms,
+            SpeedUnit::Mph => ms * 3600.0 / METERS_PER_MILE,
+            SpeedUnit::Knots => ms * 3600.0 / METERS_PER_NAUTICAL_MILE,
+            SpeedUnit::SecPerKm => 1000.0 / ms,
+            SpeedUnit::SecPerMile => METERS_PER_MILE / ms,
+            SpeedUnit::SecPer500m => 500.0 / ms,
+            SpeedUnit::SecPer100m => 100.0 / ms,
+        };
+        unit.speed(value)
+    }
+}
+
+impl fmt::Display for Speed {
+    /// Formats the speed in its unit, or `-` when it is not finite or when it is zero in a unit of
+    /// pace, as there is no pace when standing still.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.to_ms();
+        if !ms.is_finite() || (self.unit().is_pace() && ms == 0.0) {
+            return f.write_str("-");
+        }
+        match *self {
+            Speed::Kmph(value) | Speed::Mph(value) | Speed::Knots(value) => {
+                write!(f, "{:.1} {}", value, self.unit())
+            }
+            Speed::Ms(value) => write!(f, "{:.2} {}", value, self.unit()),
+            Speed::SecPerKm(pace) | Speed::SecPerMile(pace) => {
+                let seconds = pace.round() as u64;
+                write!(f, "{}:{:02}{}", seconds / 60, seconds % 60, self.unit())
+            }
+            Speed::SecPer500m(pace) | Speed::SecPer100m(pace) => {
+                let tenths = (pace * 10.0).round() as u64;
+                write!(
+                    f,
+                    "{}:{:02}.{}{}",
+                    tenths / 600,
+                    tenths / 10 % 60,
+                    tenths % 10,
+                    self.unit()
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for Speed {
+    type Err = String;
+
+    /// Parses a speed like `15.2 km/h`, `10mph` or a pace like `4:30/km`, `1:45.5/500m` or
+    /// `90/100m`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ':'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let unit: SpeedUnit = unit.trim().parse()?;
+
+        let value = if unit.is_pace() {
+            parse_duration(number)
+        } else {
+            number.parse().ok()
+        };
+        match value {
+            Some(value) if value > 0.0 => Ok(unit.speed(value)),
+            _ => Err(format!("invalid speed '{}'", s)),
+        }
+    }
+}
+
+/// Parses a duration in seconds formatted as `m:ss.s` or just seconds.
+fn parse_duration(s: &str) -> Option<f64> {
+    match s.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes = minutes.parse::<u64>().ok()?;
+            let seconds = seconds.parse::<f64>().ok()?;
+            if seconds >= 60.0 {
+                return None;
+            }
+            Some(minutes as f64 * 60.0 + seconds)
+        }
+        None => s.parse().ok(),
+    }
+}
+
+/// The units a [`Speed`] can be expressed in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpeedUnit {
+    Kmph,
+    Ms,
+    Mph,
+    Knots,
+    SecPerKm,
+    SecPerMile,
+    SecPer500m,
+    SecPer100m,
+}
+
+impl SpeedUnit {
+    /// Constructs a speed of `value` in this unit
+    pub fn speed(self, value: f64) -> Speed {
+        match self {
+            SpeedUnit::Kmph => Speed::Kmph(value),
+            SpeedUnit::Ms => Speed::Ms(value),
+            SpeedUnit::Mph => Speed::Mph(value),
+            SpeedUnit::Knots => Speed::Knots(value),
+            SpeedUnit::SecPerKm => Speed::SecPerKm(value),
+            SpeedUnit::SecPerMile => Speed::SecPerMile(value),
+            SpeedUnit::SecPer500m => Speed::SecPer500m(value),
+            SpeedUnit::SecPer100m => Speed::SecPer100m(value),
+        }
+    }
+
+    /// Returns true if the unit is a pace, i.e. time per distance
+    pub fn is_pace(self) -> bool {
+        matches!(
+            self,
+            SpeedUnit::SecPerKm
+                | SpeedUnit::SecPerMile
+                | SpeedUnit::SecPer500m
+                | SpeedUnit::SecPer100m
+        )
+    }
+}
+
+impl fmt::Display for SpeedUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            SpeedUnit::Kmph => "km/h",
+            SpeedUnit::Ms => "m/s",
+            SpeedUnit::Mph => "mph",
+            SpeedUnit::Knots => "kn",
+            SpeedUnit::SecPerKm => "/km",
+            SpeedUnit::SecPerMile => "/mi",
+            SpeedUnit::SecPer500m => "/500m",
+            SpeedUnit::SecPer100m => "/100m",
+        };
+        f.write_str(symbol)
+    }
+}
+
+impl Serialize for SpeedUnit {
+    /// Serializes the symbol of the unit, with paces in seconds like `s/km`.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_pace() {
+            serializer.collect_str(&format_args!("s{}", self))
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl FromStr for SpeedUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "km/h" | "kmh" | "kmph" | "kph" => Ok(SpeedUnit::Kmph),
+            "m/s" | "ms" | "mps" => Ok(SpeedUnit::Ms),
+            "mph" => Ok(SpeedUnit::Mph),
+            "kn" | "kt" | "kts" | "knots" => Ok(SpeedUnit::Knots),
+            "/km" | "min/km" => Ok(SpeedUnit::SecPerKm),
+            "/mi" | "/mile" | "min/mi" | "min/mile" => Ok(SpeedUnit::SecPerMile),
+            "/500m" => Ok(SpeedUnit::SecPer500m),
+            "/100m" => Ok(SpeedUnit::SecPer100m),
+            _ => Err(format!("unknown speed unit '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Speed, SpeedUnit};
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!("4:30/km".parse(), Ok(Speed::SecPerKm(270.0)));
+        assert_eq!("1:45.5/500m".parse(), Ok(Speed::SecPer500m(105.5)));
+        assert_eq!("90/100m".parse(), Ok(Speed::SecPer100m(90.0)));
+        assert_eq!("7:00 /mi".parse(), Ok(Speed::SecPerMile(420.0)));
+        assert_eq!("15.2 km/h".parse(), Ok(Speed::Kmph(15.2)));
+        assert_eq!("10mph".parse(), Ok(Speed::Mph(10.0)));
+        assert_eq!("3.5 m/s".parse(), Ok(Speed::Ms(3.5)));
+        assert_eq!("8 knots".parse(), Ok(Speed::Knots(8.0)));
+        assert!("4:75/km".parse::<Speed>().is_err());
+        assert!("0 km/h".parse::<Speed>().is_err());
+        assert!("15 furlongs".parse::<Speed>().is_err());
+    }
+
+    #[test]
+    fn test_display_speed() {
+        assert_eq!(Speed::Kmph(15.24).to_string(), "15.2 km/h");
+        assert_eq!(Speed::SecPerKm(269.7).to_string(), "4:30/km");
+        assert_eq!(Speed::SecPer500m(112.34).to_string(), "1:52.3/500m");
+        assert_eq!(Speed::SecPer500m(119.96).to_string(), "2:00.0/500m");
+        assert_eq!(Speed::Ms(3.0).to_string(), "3.00 m/s");
+        assert_eq!(Speed::Ms(0.0).to_unit(SpeedUnit::SecPerKm).to_string(), "-");
+        assert_eq!(Speed::Kmph(f64::NAN).to_string(), "-");
+        assert_eq!(Speed::Kmph(0.0).to_string(), "0.0 km/h");
+        assert_eq!(
+            serde_json::to_string(&SpeedUnit::SecPerKm).unwrap(),
+            r#""s/km""#
+        );
+        assert_eq!(
+            serde_json::to_string(&SpeedUnit::Kmph).unwrap(),
+            r#""km/h""#
+        );
+    }
+
+    #[test]
+    fn test_convert_speed() {
+        let speed = Speed::SecPerKm(240.0);
+        assert!((speed.to_kmph() - 15.0).abs() < 1e-9);
+        assert!((speed.to_unit(SpeedUnit::SecPer500m).value() - 120.0).abs() < 1e-9);
+        assert!((Speed::Mph(10.0).to_unit(SpeedUnit::SecPerMile).value() - 360.0).abs() < 1e-9);
+        assert!((Speed::Knots(1.0).to_ms() - 0.514444).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{Record, SpeedUnit};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// The sport a part of an activity was recorded as.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sport {
+    Running,
+    Cycling,
+    Swimming,
+    Rowing,
+    Other,
+}
+
+impl Sport {
+    /// Returns the sport of a TomTom `activityType`.
+    pub fn from_tomtom(activity_type: isize) -> Sport {
+        match activity_type {
+            0 | 7 | 14 => Sport::Running,
+            1 | 11 => Sport::Cycling,
+            2 => Sport::Swimming,
+            _ => Sport::Other,
+        }
+    }
+
+    /// Returns the unit speeds are commonly expressed in for this sport.
+    pub fn unit(self) -> SpeedUnit {
+        match self {
+            Sport::Running => SpeedUnit::SecPerKm,
+            Sport::Cycling | Sport::Other => SpeedUnit::Kmph,
+            Sport::Swimming => SpeedUnit::SecPer100m,
+            Sport::Rowing => SpeedUnit::SecPer500m,
+        }
+    }
+}
+
+impl FromStr for Sport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "running" | "run" => Ok(Sport::Running),
+            "cycling" | "biking" | "bike" => Ok(Sport::Cycling),
+            "swimming" | "swim" => Ok(Sport::Swimming),
+            "rowing" | "row" => Ok(Sport::Rowing),
+            "other" => Ok(Sport::Other),
+            _ => Err(format!("unknown sport '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Sport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sport::Running => write!(f, "running"),
+            Sport::Cycling => write!(f, "cycling"),
+            Sport::Swimming => write!(f, "swimming"),
+            Sport::Rowing => write!(f, "rowing"),
+            Sport::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Splits `records` into ranges of consecutive records of the same sport.
+pub fn split_by_sport(records: &[Record]) -> Vec<(Option<Sport>, Range<usize>)> {
+    let mut parts: Vec<(Option<Sport>, Range<usize>)> = Vec::new();
+    for (idx, record) in records.iter().enumerate() {
+        match parts.last_mut() {
+            Some((sport, range)) if *sport == record.sport => range.end = idx + 1,
+            _ => parts.push((record.sport, idx..idx + 1)),
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_by_sport, Sport};
+    use crate::{Record, Speed};
+
+    #[test]
+    fn test_split_by_sport() {
+        let records = [0, 0, 2, 2, 2, 1, 0]
+            .iter()
+            .enumerate()
+            .map(|(time, &activity_type)| {
+                let mut record = Record::new(time, 0.0, Speed::Ms(0.0));
+                record.sport = Some(Sport::from_tomtom(activity_type));
+                record
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            split_by_sport(&records),
+            vec![
+                (Some(Sport::Running), 0..2),
+                (Some(Sport::Swimming), 2..5),
+                (Some(Sport::Cycling), 5..6),
+                (Some(Sport::Running), 6..7),
+            ]
+        );
+        assert_eq!("Bike".parse(), Ok(Sport::Cycling));
+    }
+}
@@ -0,0 +1,160 @@
+//! Reader for Garmin Training Center (TCX) files.
+//!
+//! Every `Lap` in the file is numbered starting at 1 and stored in [`Record::lap_number`]. The
+//! distance is taken from `DistanceMeters` and falls back to the distance between positions when
+//! it is missing. Speed is read from the `TPX` extension when present and otherwise derived from
+//! the distance.
+
+use crate::geo::TrackPoint;
+use crate::xml::{child, parse_point_time, parse_text};
+use crate::{Position, Record, Speed};
+use roxmltree::Node;
+use std::io::Read;
+use std::path::Path;
+
+pub use crate::xml::Error;
+
+/// Read all records from the TCX file at `path`.
+pub fn read_path<P: AsRef<Path>>(path: P) -> Result<Vec<Record>, Error> {
+    read_str(&std::fs::read_to_string(path)?)
+}
+
+/// Read all records from TCX data provided by `reader`.
+pub fn read<R: Read>(mut reader: R) -> Result<Vec<Record>, Error> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    read_str(&text)
+}
+
+/// Read all records from a TCX document.
+pub fn read_str(text: &str) -> Result<Vec<Record>, Error> {
+    let document = roxmltree::Document::parse(text)?;
+
+    let mut records: Vec<Record> = Vec::new();
+    let mut start_time = None;
+    let mut previous: Option<TrackPoint> = None;
+
+    let laps = document
+        .descendants()
+        .filter(|node| node.has_tag_name("Lap"));
+    for (lap_index, lap) in laps.enumerate() {
+        let points = lap
+            .descendants()
+            .filter(|node| node.has_tag_name("Trackpoint"));
+        let sport = lap
+            .ancestors()
+            .find(|node| node.has_tag_name("Activity"))
+            .and_then(|activity| activity.attribute("Sport"))
+            .and_then(|sport| sport.parse().ok());
+        for point in points {
+            let time = parse_point_time(
+                child(point, "Time").ok_or(Error::MissingElement("Time"))?,
+                previous.map(|previous| previous.time),
+            )?;
+            let start_time = *start_time.get_or_insert(time);
+
+            let position = match child(point, "Position") {
+                Some(position) => Some(Position {
+                    latitude: parse_text(
+                        child(position, "LatitudeDegrees")
+                            .ok_or(Error::MissingElement("LatitudeDegrees"))?,
+                    )?,
+                    longitude: parse_text(
+                        child(position, "LongitudeDegrees")
+                            .ok_or(Error::MissingElement("LongitudeDegrees"))?,
+                    )?,
+                }),
+                None => None,
+            };
+
+            let mut speed = None;
+            let mut cadence = child(point, "Cadence").map(parse_text).transpose()?;
+            if let Some(extensions) = child(point, "Extensions") {
+                for node in extensions.descendants().filter(Node::is_element) {
+                    match node.tag_name().name() {
+                        "Speed" => speed = Some(parse_text(node)?),
+                        "RunCadence" => cadence = Some(parse_text(node)?),
+                        _ => {}
+                    }
+                }
+            }
+
+            let distance = child(point, "DistanceMeters").map(parse_text).transpose()?;
+            let track_point = TrackPoint::next(previous.as_ref(), time, distance, speed, position);
+            previous = Some(track_point);
+
+            let heart_rate = match child(point, "HeartRateBpm") {
+                Some(node) => Some(parse_text(
+                    child(node, "Value").ok_or(Error::MissingElement("Value"))?,
+                )?),
+                None => None,
+            };
+
+            records.push(Record {
+                time_in_seconds: (time - start_time).round() as usize,
+                timestamp: Some(time.round() as i64),
+                distance: track_point.distance,
+                speed: Speed::Ms(track_point.speed),
+                position,
+                elevation: child(point, "AltitudeMeters").map(parse_text).transpose()?,
+                heart_rate,
+                cadence,
+                calories: None,
+                lap_number: Some(lap_index + 1),
+                sport,
+            });
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::read_str;
+    use crate::Sport;
+
+    #[test]
+    fn test_read_tcx() {
+        let records = read_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <TrainingCenterDatabase
+                xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2"
+                xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">
+              <Activities><Activity Sport="Running">
+                <Lap StartTime="2021-09-12T08:34:47Z"><Track>
+                  <Trackpoint>
+                    <Time>2021-09-12T08:34:47Z</Time>
+                    <DistanceMeters>0.0</DistanceMeters>
+                    <HeartRateBpm><Value>120</Value></HeartRateBpm>
+                    <Extensions><ns3:TPX><ns3:Speed>0.5</ns3:Speed></ns3:TPX></Extensions>
+                  </Trackpoint>
+                  <Trackpoint>
+                    <Time>2021-09-12T08:34:49Z</Time>
+                    <DistanceMeters>6.0</DistanceMeters>
+                  </Trackpoint>
+                </Track></Lap>
+                <Lap StartTime="2021-09-12T08:34:50Z"><Track>
+                  <Trackpoint>
+                    <Time>2021-09-12T08:34:50Z</Time>
+                    <DistanceMeters>10.0</DistanceMeters>
+                    <Cadence>85</Cadence>
+                  </Trackpoint>
+                </Track></Lap>
+              </Activity></Activities>
+            </TrainingCenterDatabase>"#,
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].heart_rate, Some(120.0));
+        assert_eq!(records[0].speed.to_ms(), 0.5);
+        assert_eq!(records[0].lap_number, Some(1));
+        assert_eq!(records[1].time_in_seconds, 2);
+        assert_eq!(records[1].speed.to_ms(), 3.0);
+        assert_eq!(records[2].lap_number, Some(2));
+        assert_eq!(records[2].cadence, Some(85.0));
+        assert_eq!(records[2].speed.to_ms(), 4.0);
+        assert_eq!(records[2].sport, Some(Sport::Running));
+    }
+}
@@ -0,0 +1,282 @@
+//! Reader for the CSV files exported by TomTom sport watches.
+//!
+//! When the device did not record the distance or speed columns, they are derived from the
+//! positions of consecutive records.
+
+use crate::geo::TrackPoint;
+use crate::{Position, Record, Speed, Sport};
+use serde::Deserialize;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// The columns every TomTom CSV file has to contain
+const REQUIRED_COLUMNS: [&str; 2] = ["time", "activityType"];
+
+/// An error that occurred while reading a TomTom CSV file.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Csv(csv::Error),
+    MissingColumn(&'static str),
+    InvalidValue {
+        line: u64,
+        column: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Csv(err) => write!(f, "invalid csv: {}", err),
+            Error::MissingColumn(column) => write!(f, "missing required column '{}'", column),
+            Error::InvalidValue {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "invalid value '{}' in column '{}' on line {}",
+                value, column, line
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        Error::Csv(err)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRecord {
+    #[serde(rename(deserialize = "time"))]
+    time_in_seconds: usize,
+
+    #[serde(rename(deserialize = "activityType"))]
+    activity_type: isize,
+
+    #[serde(rename(deserialize = "lapNumber"))]
+    lap_number: Option<usize>,
+
+    distance: Option<f64>,
+
+    speed: Option<f64>,
+
+    calories: Option<usize>,
+
+    #[serde(rename(deserialize = "lat"))]
+    latitide: Option<f64>,
+
+    #[serde(rename(deserialize = "long"))]
+    longtitude: Option<f64>,
+
+    elevation: Option<f64>,
+
+    #[serde(rename(deserialize = "heartRate"))]
+    heart_rate: Option<String>,
+    cycles: Option<usize>,
+}
+
+/// Read all records from the TomTom CSV file at `path`.
+pub fn read_path<P: AsRef<Path>>(path: P) -> Result<Vec<Record>, Error> {
+    read(File::open(path)?)
+}
+
+/// Read all records from TomTom CSV data provided by `reader`.
+pub fn read<R: io::Read>(reader: R) -> Result<Vec<Record>, Error> {
+    read_records(csv::Reader::from_reader(reader))
+}
+
+fn read_records<R: io::Read>(mut reader: csv::Reader<R>) -> Result<Vec<Record>, Error> {
+    let headers = reader.headers()?.clone();
+    if let Some(column) = REQUIRED_COLUMNS
+        .iter()
+        .find(|column| !headers.iter().any(|header| header == **column))
+    {
+        return Err(Error::MissingColumn(column));
+    }
+
+    // The distance can be derived from the positions, and the speed from the distance
+    let has_column = |column| headers.iter().any(|header| header == column);
+    let has_positions = has_column("lat") && has_column("long");
+    if !(has_column("distance") || has_column("speed") || has_positions) {
+        return Err(Error::MissingColumn("distance"));
+    }
+
+    // The records are kept with their line for reporting invalid values
+    let mut records: Vec<(u64, RawRecord)> = Vec::new();
+    for row in reader.records() {
+        let row = row?;
+        let raw = row
+            .deserialize(Some(&headers))
+            .map_err(|err| invalid_value(&headers, &row, err))?;
+        records.push((row.position().map_or(0, |position| position.line()), raw));
+    }
+
+    // If the last field has activityType=-1, remove it
+    if matches!(
+        records.last(),
+        Some((
+            _,
+            RawRecord {
+                activity_type: -1,
+                ..
+            }
+        ))
+    ) {
+        records.pop();
+    }
+
+    // Convert to something we can work with
+    let mut result: Vec<Record> = Vec::with_capacity(records.len());
+    for (line, raw) in records {
+        let previous = result.last();
+        if previous.is_some_and(|previous| raw.time_in_seconds < previous.time_in_seconds) {
+            return Err(Error::InvalidValue {
+                line,
+                column: "time".to_owned(),
+                value: raw.time_in_seconds.to_string(),
+            });
+        }
+        let position = raw
+            .latitide
+            .zip(raw.longtitude)
+            .map(|(latitude, longitude)| Position {
+                latitude,
+                longitude,
+            });
+
+        let point = TrackPoint::next(
+            previous.map(TrackPoint::from).as_ref(),
+            raw.time_in_seconds as f64,
+            raw.distance,
+            raw.speed,
+            position,
+        );
+
+        result.push(Record {
+            // The cycles column holds the number of steps or strokes since the previous record
+            cadence: match (raw.cycles, previous) {
+                (Some(cycles), Some(previous))
+                    if raw.time_in_seconds > previous.time_in_seconds =>
+                {
+                    Some(
+                        cycles as f64 * 60.0
+                            / (raw.time_in_seconds - previous.time_in_seconds) as f64,
+                    )
+                }
+                _ => None,
+            },
+            time_in_seconds: raw.time_in_seconds,
+            timestamp: None,
+            distance: point.distance,
+            speed: Speed::Ms(point.speed),
+            position,
+            elevation: raw.elevation,
+            heart_rate: match raw.heart_rate.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(heart_rate) => Some(heart_rate.parse().map_err(|_| Error::InvalidValue {
+                    line,
+                    column: "heartRate".to_owned(),
+                    value: heart_rate.to_owned(),
+                })?),
+            },
+            calories: raw.calories.map(|calories| calories as f64),
+            lap_number: raw.lap_number,
+            sport: Some(Sport::from_tomtom(raw.activity_type)),
+        });
+    }
+    Ok(result)
+}
+
+/// Converts an error deserializing `row` into an error naming the line and column of the value.
+fn invalid_value(headers: &csv::StringRecord, row: &csv::StringRecord, err: csv::Error) -> Error {
+    let field = match err.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.field(),
+        _ => None,
+    };
+    match (field, row.position()) {
+        (Some(field), Some(position)) => Error::InvalidValue {
+            line: position.line(),
+            column: headers.get(field as usize).unwrap_or_default().to_owned(),
+            value: row.get(field as usize).unwrap_or_default().to_owned(),
+        },
+        _ => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read, Error};
+
+    #[test]
+    fn test_derive_distance_and_speed() {
+        let data = "\
+time,activityType,lat,long
+0,1,52.188472,5.986998
+2,1,52.188434,5.987043
+4,1,52.188434,5.987043
+";
+        let records = read(data.as_bytes()).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].distance, 0.0);
+        assert!((records[1].distance - 5.22).abs() < 0.01);
+        assert!((records[1].speed.to_ms() - 2.61).abs() < 0.01);
+        assert!((records[2].distance - records[1].distance).abs() < 1e-9);
+        assert_eq!(records[2].speed.to_ms(), 0.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let missing = "time,distance\n0,0.0\n";
+        assert!(matches!(
+            read(missing.as_bytes()),
+            Err(Error::MissingColumn("activityType"))
+        ));
+
+        let invalid = "time,activityType,distance\n0,1,0.0\n1,1,fast\n";
+        match read(invalid.as_bytes()) {
+            Err(Error::InvalidValue {
+                line,
+                column,
+                value,
+            }) => {
+                assert_eq!(line, 3);
+                assert_eq!(column, "distance");
+                assert_eq!(value, "fast");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let no_distance = "time,activityType,lat\n0,1,52.188472\n";
+        assert!(matches!(
+            read(no_distance.as_bytes()),
+            Err(Error::MissingColumn("distance"))
+        ));
+
+        let backwards = "time,activityType,speed\n0,1,0.0\n5,1,0.0\n3,1,0.0\n";
+        assert!(matches!(
+            read(backwards.as_bytes()),
+            Err(Error::InvalidValue { line: 4, column, .. }) if column == "time"
+        ));
+
+        let heart_rate = "time,activityType,distance,heartRate\n0,1,0.0,120\n1,1,3.0,high\n";
+        assert!(matches!(
+            read(heart_rate.as_bytes()),
+            Err(Error::InvalidValue { line: 3, column, .. }) if column == "heartRate"
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+//! Planned structured workouts and aligning them with recorded sessions.
+//!
+//! A workout is written as a comma separated list of steps, where each step is a distance
+//! (`400m`, `1.5km`) or a duration (`90s`, `3min`, `2:30`). Steps can be repeated with `N x` and
+//! grouped with parentheses, and a rest can be added after every repetition with `/`:
+//!
+//! ```text
+//! 8 x 400m / 90s
+//! 3 x (1000m, 500m)
+//! 2km, 4 x 3min / 2min, 2km
+//! ```
+//!
+//! Rest steps describe the plan but are not aligned, the session is matched on its work steps.
+
+use crate::{IntervalInfo, Record, SpeedUnit};
+use serde::Serialize;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// The most reps a workout can contain, which keeps aligning it with a session tractable
+const MAX_REPS: usize = 1000;
+
+/// The goal of a single planned step.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Target {
+    /// Distance in meters
+    Distance(f64),
+    /// Duration in seconds
+    Duration(usize),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Distance(meters) => write!(f, "{}m", meters),
+            Target::Duration(seconds) => write!(f, "{}s", seconds),
+        }
+    }
+}
+
+/// A planned structured workout, flattened into the work steps in the order they are performed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workout {
+    pub reps: Vec<Target>,
+}
+
+impl FromStr for Workout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let reps = parser.sequence()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        if reps.is_empty() {
+            return Err("workout does not contain any steps".to_owned());
+        }
+        Ok(Workout { reps })
+    }
+}
+
+/// Recursive descent parser for the workout syntax.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {} of workout", message, self.pos + 1)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// sequence = item { "," item }
+    fn sequence(&mut self) -> Result<Vec<Target>, String> {
+        let mut reps = self.item()?;
+        while self.eat(',') {
+            reps.extend(self.item()?);
+            if reps.len() > MAX_REPS {
+                return Err(self.too_many_reps());
+            }
+        }
+        Ok(reps)
+    }
+
+    /// item = [ count "x" ] ( target | "(" sequence ")" ) [ "/" target ]
+    fn item(&mut self) -> Result<Vec<Target>, String> {
+        let start = self.pos;
+        let count = self.take_while(|c| c.is_ascii_digit());
+        let count = if !count.is_empty() && (self.eat('x') || self.eat('X')) {
+            count.parse::<usize>().map_err(|err| err.to_string())?
+        } else {
+            self.pos = start;
+            1
+        };
+
+        let reps = if self.eat('(') {
+            let reps = self.sequence()?;
+            if !self.eat(')') {
+                return Err(self.error("expected ')'"));
+            }
+            reps
+        } else {
+            vec![self.target()?]
+        };
+
+        // The rest is only part of the plan, it is not aligned with the session
+        if self.eat('/') {
+            self.target()?;
+        }
+
+        if reps.len().saturating_mul(count) > MAX_REPS {
+            return Err(self.too_many_reps());
+        }
+        Ok(reps.repeat(count))
+    }
+
+    fn too_many_reps(&self) -> String {
+        self.error(&format!("workout has more than {} reps", MAX_REPS))
+    }
+
+    /// target = number ( "m" | "km" | "s" | "sec" | "min" ) | minutes ":" seconds
+    fn target(&mut self) -> Result<Target, String> {
+        let number = self.take_while(|c| c.is_ascii_digit() || c == '.' || c == ':');
+        if number.is_empty() {
+            return Err(self.error("expected a distance or duration"));
+        }
+        let invalid = || format!("invalid number '{}' in workout", number);
+
+        if let Some((minutes, seconds)) = number.split_once(':') {
+            let minutes = minutes.parse::<usize>().map_err(|_| invalid())?;
+            let seconds = seconds.parse::<usize>().map_err(|_| invalid())?;
+            if seconds >= 60 {
+                return Err(invalid());
+            }
+            return Ok(Target::Duration(minutes * 60 + seconds));
+        }
+
+        let value = number.parse::<f64>().map_err(|_| invalid())?;
+        let unit = self.take_while(|c| c.is_ascii_alphabetic());
+        match unit.to_ascii_lowercase().as_str() {
+            "m" => Ok(Target::Distance(value)),
+            "km" => Ok(Target::Distance(value * 1000.0)),
+            "s" | "sec" => Ok(Target::Duration(value.round() as usize)),
+            "min" => Ok(Target::Duration((value * 60.0).round() as usize)),
+            _ => Err(self.error(&format!("unknown unit '{}'", unit))),
+        }
+    }
+}
+
+/// Result of a planned rep aligned with the recorded session.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepInfo {
+    /// Number of the rep in the plan, starting at 1
+    pub rep: usize,
+
+    /// The planned distance or duration of the rep
+    pub planned: String,
+
+    /// Time in seconds since the start of the activity
+    pub start_time: usize,
+
+    /// Duration in seconds
+    pub duration: usize,
+
+    /// Distance in meters
+    pub distance: usize,
+
+    /// Average speed in the unit the rep was summarized in
+    pub average_speed: f64,
+
+    /// The unit the speed is expressed in, e.g. `km/h` or `s/km`
+    pub unit: SpeedUnit,
+}
+
+/// Aligns the reps of `workout` with `records`.
+///
+/// Every rep is matched with a segment of the session that covers its planned distance or
+/// duration. The segments are chosen in order without overlapping such that the sum of their
+/// average speeds is maximal, so the reps end up on the hardest efforts of the session. Returns
+/// `None` if the session is too short to fit all reps.
+pub fn match_workout(records: &[Record], workout: &Workout) -> Option<Vec<Range<usize>>> {
+    let count = records.len();
+    let reps = &workout.reps;
+
+    // best[rep][start] is the maximum sum of average speeds of reps `rep..` when they start at or
+    // after record `start`, together with the choice that leads to it.
+    let mut best = vec![vec![None; count + 1]; reps.len() + 1];
+    best[reps.len()] = vec![Some((0.0, None)); count + 1];
+    for (rep, target) in reps.iter().enumerate().rev() {
+        for start in (0..count).rev() {
+            let mut candidate = best[rep][start + 1].map(|(score, _)| (score, None));
+            if let Some(end) = rep_end(records, start, *target) {
+                if let Some((remaining, _)) = best[rep + 1][end] {
+                    let first = &records[start];
+                    let last = &records[end - 1];
+                    let duration = (last.time_in_seconds - first.time_in_seconds) as f64;
+                    let score = remaining + (last.distance - first.distance) / duration;
+                    if candidate.is_none_or(|(best_score, _)| score > best_score) {
+                        candidate = Some((score, Some(end)));
+                    }
+                }
+            }
+            best[rep][start] = candidate;
+        }
+    }
+
+    // Follow the choices to find the segment of each rep
+    best[0][0]?;
+    let mut ranges = Vec::with_capacity(reps.len());
+    let mut start = 0;
+    for choices in &best[..reps.len()] {
+        loop {
+            if let (_, Some(end)) = choices[start]? {
+                ranges.push(start..end);
+                start = end;
+                break;
+            }
+            start += 1;
+        }
+    }
+    Some(ranges)
+}
+
+/// Returns the exclusive end of a rep starting at `start` that covers `target`, or `None` if the
+/// session ends before that.
+fn rep_end(records: &[Record], start: usize, target: Target) -> Option<usize> {
+    let first = &records[start];
+    let offset = records[start..].partition_point(|rec| match target {
+        Target::Distance(distance) => rec.distance - first.distance < distance,
+        Target::Duration(duration) => rec.time_in_seconds - first.time_in_seconds < duration,
+    });
+    let last = records.get(start + offset)?;
+    if last.time_in_seconds > first.time_in_seconds {
+        Some(start + offset + 1)
+    } else {
+        None
+    }
+}
+
+/// Summarizes the aligned reps of `workout`, with speeds expressed in `unit`.
+pub fn rep_infos(
+    records: &[Record],
+    workout: &Workout,
+    ranges: &[Range<usize>],
+    unit: SpeedUnit,
+) -> Vec<RepInfo> {
+    workout
+        .reps
+        .iter()
+        .zip(ranges)
+        .enumerate()
+        .map(|(idx, (target, range))| {
+            let info = IntervalInfo::from_range(records, range.clone(), unit);
+            RepInfo {
+                rep: idx + 1,
+                planned: target.to_string(),
+                start_time: info.start_time,
+                duration: info.duration,
+                distance: info.distance,
+                average_speed: info.average_speed,
+                unit,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{match_workout, Target, Workout};
+    use crate::{Record, Speed};
+
+    #[test]
+    fn test_parse_workout() {
+        let workout: Workout = "2 x 400m / 90s".parse().unwrap();
+        assert_eq!(
+            workout.reps,
+            vec![Target::Distance(400.0), Target::Distance(400.0)]
+        );
+
+        let workout: Workout = "1.5km, 2 x (3min, 1:30)".parse().unwrap();
+        assert_eq!(
+            workout.reps,
+            vec![
+                Target::Distance(1500.0),
+                Target::Duration(180),
+                Target::Duration(90),
+                Target::Duration(180),
+                Target::Duration(90),
+            ]
+        );
+
+        assert!("3 x (400m".parse::<Workout>().is_err());
+        assert!("400 miles".parse::<Workout>().is_err());
+        assert!("".parse::<Workout>().is_err());
+        assert!("1:75".parse::<Workout>().is_err());
+        assert!("1000 x 400m".parse::<Workout>().is_ok());
+        assert!("1001 x 400m".parse::<Workout>().is_err());
+        assert!("1000 x (400m, 200m)".parse::<Workout>().is_err());
+        assert!("99999999999999999999 x 400m".parse::<Workout>().is_err());
+        assert!("999 x 400m, 2 x 200m".parse::<Workout>().is_err());
+    }
+
+    #[test]
+    fn test_match_workout() {
+        // Alternate 10 seconds at 2 m/s and 10 seconds at 5 m/s
+        let mut distance = 0.0;
+        let records = (0..60)
+            .map(|time| {
+                let speed = if (time / 10) % 2 == 1 { 5.0 } else { 2.0 };
+                let record = Record::new(time, distance, Speed::Ms(speed));
+                distance += speed;
+                record
+            })
+            .collect::<Vec<_>>();
+
+        let workout: Workout = "2 x 50m".parse().unwrap();
+        assert_eq!(
+            match_workout(&records, &workout),
+            Some(vec![10..21, 30..41])
+        );
+
+        let workout: Workout = "1km".parse().unwrap();
+        assert_eq!(match_workout(&records, &workout), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Helpers shared by the XML based readers.
+
+use chrono::DateTime;
+use roxmltree::Node;
+use std::fmt;
+use std::io;
+
+/// An error that occurred while reading an XML based activity file.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Xml(roxmltree::Error),
+    InvalidValue {
+        line: u32,
+        element: String,
+        value: String,
+    },
+    MissingElement(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Xml(err) => write!(f, "invalid xml: {}", err),
+            Error::InvalidValue {
+                line,
+                element,
+                value,
+            } => write!(
+                f,
+                "invalid value '{}' for <{}> on line {}",
+                value, element, line
+            ),
+            Error::MissingElement(element) => write!(f, "track point is missing <{}>", element),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(err: roxmltree::Error) -> Self {
+        Error::Xml(err)
+    }
+}
+
+pub fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+pub fn parse_attribute(node: Node, name: &'static str) -> Result<f64, Error> {
+    let value = node.attribute(name).ok_or(Error::MissingElement(name))?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid_value(node, name, value))
+}
+
+pub fn parse_text(node: Node) -> Result<f64, Error> {
+    let value = node.text().unwrap_or_default();
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid_value(node, node.tag_name().name(), value))
+}
+
+/// Parses an RFC 3339 timestamp into seconds since the unix epoch.
+pub fn parse_time(node: Node) -> Result<f64, Error> {
+    let value = node.text().unwrap_or_default();
+    let time = DateTime::parse_from_rfc3339(value.trim())
+        .map_err(|_| invalid_value(node, node.tag_name().name(), value))?;
+    Ok(time.timestamp_millis() as f64 / 1000.0)
+}
+
+/// Parses the time of a point like [`parse_time`], which can't be before the `previous` time.
+pub fn parse_point_time(node: Node, previous: Option<f64>) -> Result<f64, Error> {
+    let time = parse_time(node)?;
+    match previous {
+        Some(previous) if time < previous => Err(invalid_value(
+            node,
+            node.tag_name().name(),
+            node.text().unwrap_or_default(),
+        )),
+        _ => Ok(time),
+    }
+}
+
+fn invalid_value(node: Node, element: &str, value: &str) -> Error {
+    Error::InvalidValue {
+        line: node.document().text_pos_at(node.range().start).row,
+        element: element.to_owned(),
+        value: value.to_owned(),
+    }
+}